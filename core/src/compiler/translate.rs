@@ -1,14 +1,38 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+
 use anyhow::{anyhow, Result};
 use log::info;
 
 use crate::parser::literals::{Literal, StrictNumber, VagueLiteral};
-use crate::parser::ops::Op;
-use crate::parser::Token;
+use crate::parser::ops::{Op, UnaryOp};
+use crate::parser::{functions, groups, variants, Pattern, Token};
 
 const CORE_PRELUDE: &str = r#"
 
 "#;
 
+/// Only added to files that use ranges, since V's own can't count down. It stops once it's gone
+/// as far as `end` rather than when it gets there, and never steps past it, so it can't overflow.
+const RANGE: &str = r#"
+fn dl_range[T](start T, end T, inclusive bool) []T {
+	mut values := []T{}
+	mut i := start
+	for (start < end && i < end) || (start > end && i > end) {
+		values << i
+		if start < end {
+			i++
+		} else {
+			i--
+		}
+	}
+	if inclusive {
+		values << end
+	}
+	return values
+}
+"#;
+
 macro_rules! you_asked {
     ($req: expr, $didntget: expr) => {
         format!(
@@ -48,56 +72,382 @@ fn transform_literal(lit: &str) -> Result<String> {
         "largesigned" => "i64",
         "mediumfloat" => "f32",
         "largefloat" => "f64",
+        "boolean" => "bool",
+        "string" => "string",
+        "character" => "rune",
         _ => return Err(anyhow!("Failed to parse literal identifier")),
     }
     .to_string());
 }
 
+/// The V types of a function's parameters, and of what it returns
+type Signature = (Vec<String>, Option<String>);
+
+/// Writes text out the way V reads it between `quote`s. A `$` is escaped in strings too, since it
+/// would start an interpolation.
+fn escape(text: &str, quote: char) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '$' if quote == '"' => escaped.push_str("\\$"),
+            c if c == quote => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// The names in the file being translated that V spells differently than dolomite does
+#[derive(Default)]
+struct Scope {
+    /// The groups in the file, along with their variants
+    groups: Vec<(String, Vec<String>)>,
+    /// Variants that were aliased, along with their group
+    variants: Vec<(String, String)>,
+    /// How many values the function being translated returns, if it returns a tuple
+    tuple_return: Option<usize>,
+    /// The V types of the function the function being translated returns, if it returns one
+    function_return: Option<Signature>,
+    /// The functions declared so far, along with the types each of their parameters takes when
+    /// it takes a function
+    functions: Vec<(String, Vec<Option<Signature>>)>,
+    /// How many values each function gives back, the ones at the top of the file from the start
+    /// and the ones inside others once they're declared
+    returns: Vec<(String, usize)>,
+    /// Helpers the file needs that aren't in `CORE_PRELUDE`
+    helpers: Vec<&'static str>,
+    /// The names declared in each block around the statement being translated, innermost last,
+    /// along with whether they're `mut`
+    bindings: Vec<Vec<(String, bool)>>,
+    /// Where the blocks of the outermost function being translated start in `bindings`
+    locals: Option<usize>,
+    /// The parameters of the lambdas being translated, along with the values they were given
+    arguments: Vec<(String, String)>,
+    /// The names the closure being translated reaches through a `{name}_ref` reference, since
+    /// it or one inside it assigns to them
+    references: Vec<String>,
+    /// Lines to put before the statement being translated, when it starts with a `match` or a
+    /// pipeline that has to name a value first. `None` anywhere else.
+    leading: Option<Vec<String>>,
+    /// How many values have been named so far, so each name is new
+    named: usize,
+    /// How many pipelines going through an iterator with a `for` loop are around the one being
+    /// translated, so each loop names its element differently
+    loops: usize,
+}
+
+impl Scope {
+    fn new(file: &Token) -> Self {
+        Self {
+            groups: groups(file)
+                .iter()
+                .map(|(name, variants)| {
+                    (
+                        name.to_string(),
+                        variants.iter().map(|variant| variant.to_string()).collect(),
+                    )
+                })
+                .collect(),
+            variants: variants(file)
+                .iter()
+                .map(|(group, variant)| (group.to_string(), variant.to_string()))
+                .collect(),
+            tuple_return: None,
+            function_return: None,
+            functions: Vec::new(),
+            returns: functions(file)
+                .iter()
+                .map(|(name, returns)| (name.to_string(), values(*returns)))
+                .collect(),
+            helpers: Vec::new(),
+            bindings: Vec::new(),
+            locals: None,
+            arguments: Vec::new(),
+            references: Vec::new(),
+            leading: None,
+            named: 0,
+            loops: 0,
+        }
+    }
+
+    fn group(&self, name: &str) -> Option<String> {
+        self.groups
+            .iter()
+            .find(|(group, _)| group == name)
+            .map(|(group, _)| type_name(group))
+    }
+
+    /// How many values a function gives back, if `name` is one
+    fn returns(&self, name: &str) -> Option<usize> {
+        self.returns
+            .iter()
+            .rev()
+            .find(|(function, _)| function == name)
+            .map(|(_, values)| *values)
+    }
+
+    fn need(&mut self, helper: &'static str) {
+        if !self.helpers.contains(&helper) {
+            self.helpers.push(helper);
+        }
+    }
+
+    /// Whether a name that's already declared is `mut`, or `None` if it's new
+    fn binding(&self, name: &str) -> Option<bool> {
+        self.bindings
+            .iter()
+            .rev()
+            .flatten()
+            .find(|(binding, _)| binding == name)
+            .map(|(_, mutable)| *mutable)
+    }
+
+    fn declare(&mut self, name: &str, mutable: bool) {
+        if let Some(block) = self.bindings.last_mut() {
+            block.push((name.to_string(), mutable));
+        }
+    }
+
+    /// The group an aliased variant belongs to
+    fn variant(&self, name: &str) -> Option<String> {
+        self.variants
+            .iter()
+            .find(|(_, variant)| variant == name)
+            .map(|(group, _)| type_name(group))
+    }
+
+    /// The group a variant that wasn't aliased belongs to
+    fn unaliased(&self, name: &str) -> Option<String> {
+        if self.variant(name).is_some() {
+            return None;
+        }
+
+        self.groups
+            .iter()
+            .find(|(_, variants)| variants.iter().any(|variant| variant == name))
+            .map(|(group, _)| group.clone())
+    }
+
+    /// Whether the variants a match has arms for are every variant of a group, so V knows
+    /// nothing's left out
+    fn covers(&self, variants: &[String]) -> bool {
+        self.groups
+            .iter()
+            .any(|(_, all)| !all.is_empty() && all.iter().all(|variant| variants.contains(variant)))
+    }
+}
+
+thread_local! {
+    static SCOPE: RefCell<Scope> = RefCell::new(Scope::default());
+}
+
+/// Translates something with a block of its own, so whatever's declared inside is forgotten
+/// afterwards. `declared` starts the block off, for a function's parameters or a match binding.
+fn scoped(declared: Vec<(String, bool)>, inner: impl FnOnce() -> Result<String>) -> Result<String> {
+    SCOPE.with(|s| s.borrow_mut().bindings.push(declared));
+    let translated = inner();
+    SCOPE.with(|s| s.borrow_mut().bindings.pop());
+
+    translated
+}
+
+/// Translates a statement that starts by working out a `match` or a pipeline, so the match can
+/// name what it's on, or a lambda in the pipeline what it's given, in a line before the statement
+fn leading(inner: impl FnOnce() -> Result<String>) -> Result<String> {
+    SCOPE.with(|s| s.borrow_mut().leading = Some(Vec::new()));
+    let translated = inner();
+    let lines = SCOPE
+        .with(|s| s.borrow_mut().leading.take())
+        .unwrap_or_default();
+
+    Ok(lines
+        .into_iter()
+        .chain([translated?])
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+fn binding(name: &str) -> Option<bool> {
+    SCOPE.with(|s| s.borrow().binding(name))
+}
+
+fn declare(name: &str, mutable: bool) {
+    SCOPE.with(|s| s.borrow_mut().declare(name, mutable))
+}
+
+/// The reference a closure reaches a name through, if it doesn't have the name itself
+fn reference(name: &str) -> Option<String> {
+    SCOPE.with(|s| {
+        s.borrow()
+            .references
+            .iter()
+            .find(|reference| *reference == name)
+            .map(|name| format!("{name}_ref"))
+    })
+}
+
+/// Translates the body of a closure that reaches `references` through references
+fn referencing(references: Vec<String>, inner: impl FnOnce() -> Result<String>) -> Result<String> {
+    let outer = SCOPE.with(|s| std::mem::replace(&mut s.borrow_mut().references, references));
+    let translated = inner();
+    SCOPE.with(|s| s.borrow_mut().references = outer);
+
+    translated
+}
+
+/// What a name is assigned through: itself, or what its reference points at
+fn target(name: Token) -> Result<String> {
+    match reference(&name.to_string()) {
+        Some(reference) => Ok(format!("*{reference}")),
+        None => translate(box name),
+    }
+}
+
+/// Checks that a name can be assigned to, and gives back whether it's already declared. A name
+/// is declared by its first assignment, after which only a `mut` one can be assigned to again.
+fn assignable(name: &str, mutable: bool) -> Result<bool> {
+    match (binding(name), mutable) {
+        (None, _) => Ok(false),
+        (Some(true), false) => Ok(true),
+        (Some(false), false) => Err(anyhow!(
+            "{name} can't be assigned to again, since it isn't mut"
+        )),
+        (Some(_), true) => Err(anyhow!(
+            "{name} is already declared, so it can't be declared again with mut"
+        )),
+    }
+}
+
+/// V type names are PascalCase: `direction` is `Direction`
+fn type_name(group: &str) -> String {
+    group
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// V enum values are snake_case: `NorthEast` is `north_east`
+fn variant_name(variant: &str) -> String {
+    let mut name = String::new();
+    for (u, c) in variant.chars().enumerate() {
+        if c.is_uppercase() && u > 0 && !name.ends_with('_') {
+            name.push('_');
+        }
+        name.extend(c.to_lowercase());
+    }
+
+    name
+}
+
 pub fn translate_file(source: Box<Token>) -> Result<String> {
-    Ok(format!("{CORE_PRELUDE}\n{}", translate(source)?))
+    SCOPE.with(|scope| *scope.borrow_mut() = Scope::new(&source));
+    let translated = translate(source)?;
+    let helpers = SCOPE.with(|scope| scope.borrow().helpers.concat());
+
+    Ok(format!("{CORE_PRELUDE}\n{translated}{helpers}"))
 }
 
 fn translate(source: Box<Token>) -> Result<String> {
     info!("translate {source}");
     match source.clone() {
         box Token::Assignment {
+            doc,
             mutable,
             type_annotation,
             ident,
             value,
-        } => match value {
-            box Token::FnPair(_, _, _) => gen_function(source),
-            val => Ok(format!(
-                "{}{} := {}{}{}",
-                if mutable {
-                    info!("\tmut assignment from {source}");
-                    "mut "
-                } else {
-                    info!("{:?}", val);
-                    info!("\tassignment from {source}");
-                    ""
-                },
-                translate(ident)?,
-                if let Some(t) = type_annotation.clone() {
-                    format!("{}(", translate(t)?)
-                } else {
-                    String::new()
-                },
-                translate(val)?,
-                if type_annotation.is_some() { ")" } else { "" },
-            )),
-        },
+        } => Ok(format!(
+            "{}{}",
+            doc.iter()
+                .map(|line| format!("//{line}\n"))
+                .collect::<String>(),
+            match (value, ident) {
+                (box Token::Function { .. }, _) => gen_function(source)?,
+                // V declares or updates every name it takes a tuple apart into at once:
+                // `a, b := b, a`
+                (val, box Token::Array(names)) => {
+                    let declared = names
+                        .iter()
+                        .map(|name| assignable(&name.to_string(), mutable))
+                        .collect::<Result<Vec<bool>>>()?;
+                    let update = declared.iter().all(|d| *d);
+                    if !update && declared.contains(&true) {
+                        return Err(anyhow!(
+                            "V can't declare some names and update others at once, in {source}"
+                        ));
+                    }
+
+                    let value = match val {
+                        box Token::Array(values) => translate_tuple(values)?,
+                        val => translate(val)?,
+                    };
+                    let names = names
+                        .into_iter()
+                        .map(|name| {
+                            if !update {
+                                declare(&name.to_string(), mutable);
+                            }
+
+                            Ok(format!(
+                                "{}{}",
+                                if mutable { "mut " } else { "" },
+                                target(name)?
+                            ))
+                        })
+                        .collect::<Result<Vec<String>>>()?
+                        .join(", ");
+
+                    format!("{names} {} {value}", if update { "=" } else { ":=" })
+                }
+                (val, target @ box Token::Index { .. }) => {
+                    info!("\tindex assignment from {source}");
+                    if let Some(name) = indexed(&target) {
+                        assignable(&name, false)?;
+                    }
+
+                    format!("{} = {}", translate(target)?, translate(val)?)
+                }
+                (val, ident) => {
+                    let name = ident.to_string();
+                    let update = assignable(&name, mutable)?;
+                    let value = translate_typed(val, type_annotation)?;
+                    if update {
+                        info!("\treassignment from {source}");
+                        format!("{} = {value}", target(*ident)?)
+                    } else {
+                        info!("\tassignment from {source}");
+                        declare(&name, mutable);
+                        format!(
+                            "{}{} := {value}",
+                            if mutable { "mut " } else { "" },
+                            translate(ident)?
+                        )
+                    }
+                }
+            }
+        )),
 
         box Token::MethodUnit(name, args) => match (name, args) {
             (box Token::Ident(name_raw), box Token::Array(args_raw)) => {
                 info!("\tmultiarg methodunit from {source}");
                 Ok(format!(
                     "{name_raw}({})",
-                    args_raw
-                        .iter()
-                        .map(|s| format!("{s}"))
-                        .collect::<Vec<String>>()
-                        .join(", ")
+                    call_arguments(name_raw.fragment(), args_raw)?
                 ))
             }
 
@@ -106,7 +456,7 @@ fn translate(source: Box<Token>) -> Result<String> {
                 Ok(format!(
                     "{}({})",
                     translate(box Token::Ident(name_raw))?,
-                    translate(arg)?
+                    call_arguments(name_raw.fragment(), vec![*arg])?
                 ))
             }
 
@@ -120,54 +470,304 @@ fn translate(source: Box<Token>) -> Result<String> {
                     info!("\tvagueint from {source}");
                     Ok(format!("{i}"))
                 }
+                VagueLiteral::Float(f) => {
+                    info!("\tvaguefloat from {source}");
+                    // Debug keeps the decimal point on whole numbers, so V still sees a float
+                    Ok(format!("{f:?}"))
+                }
                 _ => unimplemented!(),
             },
             Literal::String(s) => {
                 info!("\tstring form {source}");
-                Ok(format!("\"{s}\""))
+                Ok(format!("\"{}\"", escape(&s, '"')))
+            }
+            Literal::Char(c) => {
+                info!("\tchar from {source}");
+                Ok(format!("`{}`", escape(&c.to_string(), '`')))
+            }
+            Literal::Bool(b) => {
+                info!("\tbool from {source}");
+                Ok(format!("{b}"))
+            }
+            Literal::Number(strictnum) => {
+                info!("\tstrict number from {source}");
+                Ok(match strictnum {
+                    StrictNumber::Byte(i) => format!("u8({i})"),
+                    StrictNumber::ByteSigned(i) => format!("i8({i})"),
+                    StrictNumber::Small(i) => format!("u16({i})"),
+                    StrictNumber::SmallSigned(i) => format!("i16({i})"),
+                    StrictNumber::Medium(i) => format!("u32({i})"),
+                    StrictNumber::MediumSigned(i) => format!("i32({i})"),
+                    StrictNumber::MediumFloat(i) => format!("f32({i:?})"),
+                    StrictNumber::Large(i) => format!("u64({i})"),
+                    StrictNumber::LargeSigned(i) => format!("i64({i})"),
+                    StrictNumber::LargeFloat(i) => format!("f64({i:?})"),
+                })
             }
-            Literal::Number(strictnum) => match strictnum {
-                StrictNumber::Byte(i) => {
-                    info!("\tbyte from {source}");
-                    Ok(format!("u8({i})"))
-                }
-                _ => unimplemented!(),
-            },
-            _ => unimplemented!(),
         },
 
-        box Token::Pair(former, latter) => {
-            info!("\tpair from {source}");
-            Ok(format!("{}\n{}", translate(former)?, translate(latter)?))
+        box Token::Block(statements) => {
+            info!("\tblock from {source}");
+            scoped(Vec::new(), || {
+                Ok(statements
+                    .into_iter()
+                    .map(|statement| match statement {
+                        Token::Match { value, arms } => {
+                            leading(|| translate_match(value, arms, true))
+                        }
+                        statement @ (Token::Method(..)
+                        | Token::Return(Some(box Token::Method(..)))
+                        | Token::Assignment {
+                            ident: box Token::Ident(_),
+                            value: box Token::Match { .. } | box Token::Method(..),
+                            ..
+                        }) => leading(|| translate(box statement)),
+                        statement => translate(box statement),
+                    })
+                    .collect::<Result<Vec<String>>>()?
+                    .join("\n"))
+            })
         }
 
-        box Token::CondPair(cond, block, latter) => {
-            info!("\tcondpair from {source}");
+        box Token::IfStmt {
+            cond,
+            block,
+            else_branch,
+        } => {
+            info!("\tifstmt from {source}");
             Ok(format!(
-                "if {} {{\n{}\n}}\n {}",
+                "if {} {{\n{}\n}}{}",
                 translate(cond)?,
                 translate(block)?,
-                translate(latter)?
+                match else_branch {
+                    Some(branch @ box Token::IfStmt { .. }) =>
+                        format!(" else {}", translate(branch)?),
+                    Some(branch) => format!(" else {{\n{}\n}}", translate(branch)?),
+                    None => String::new(),
+                }
+            ))
+        }
+
+        box Token::Loop { label, cond, block } => {
+            info!("\tloop from {source}");
+            Ok(format!(
+                "{}for {}{{\n{}\n}}",
+                match label {
+                    Some(label) => format!("{label}: "),
+                    None => String::new(),
+                },
+                match cond {
+                    Some(cond) => format!("{} ", translate(cond)?),
+                    None => String::new(),
+                },
+                translate(block)?
+            ))
+        }
+
+        box Token::Break { label, .. } => match label {
+            Some(label) => Ok(format!("break {label}")),
+            None => Ok("break".to_string()),
+        },
+
+        box Token::Continue { label, .. } => match label {
+            Some(label) => Ok(format!("continue {label}")),
+            None => Ok("continue".to_string()),
+        },
+
+        // A V match can't give back a tuple, so each arm returns instead
+        box Token::Return(Some(box Token::Match { value, arms })) => leading(|| {
+            translate_match(
+                value,
+                arms.into_iter()
+                    .map(|(pattern, t)| (pattern, Token::Return(Some(box t))))
+                    .collect(),
+                false,
+            )
+        }),
+
+        box Token::Return(Some(box Token::Array(values)))
+            if SCOPE.with(|s| s.borrow().tuple_return.is_some()) =>
+        {
+            let expected = SCOPE.with(|s| s.borrow().tuple_return).unwrap();
+            if values.len() != expected {
+                return Err(anyhow!(
+                    "Returned {} values, but the function returns a tuple of {expected}",
+                    values.len()
+                ));
+            }
+
+            Ok(format!("return {}", translate_tuple(values)?))
+        }
+
+        box Token::Return(Some(box lambda @ Token::Lambda { .. }))
+            if SCOPE.with(|s| s.borrow().function_return.is_some()) =>
+        {
+            let signature = SCOPE.with(|s| s.borrow().function_return.clone()).unwrap();
+            Ok(format!("return {}", typed_lambda(lambda, signature)?))
+        }
+
+        box Token::Return(Some(box function @ Token::Function { .. })) => {
+            let signature = SCOPE.with(|s| s.borrow().function_return.clone());
+            Ok(format!("return {}", anonymous(function, signature)?))
+        }
+
+        box Token::Return(value) => match value {
+            Some(value) => Ok(format!("return {}", translate(value)?)),
+            None => Ok("return".to_string()),
+        },
+
+        box Token::Match { value, arms } => {
+            info!("\tmatch from {source}");
+            translate_match(value, arms, false)
+        }
+
+        box Token::Array(items) => {
+            info!("\tarray from {source}");
+            if items.is_empty() {
+                return Err(anyhow!(
+                    "An empty array needs a type annotation, like array[medium]"
+                ));
+            }
+
+            Ok(format!(
+                "[{}]",
+                items
+                    .into_iter()
+                    .map(|item| translate(box item))
+                    .collect::<Result<Vec<String>>>()?
+                    .join(", ")
             ))
         }
 
-        box Token::IfStmt { cond } => Ok(format!("{}", translate(cond)?)),
+        box Token::Index { value, index } => {
+            Ok(format!("{}[{}]", translate(value)?, translate(index)?))
+        }
+
+        box Token::ArrayType(t) => Ok(format!("[]{}", translate(t)?)),
+
+        box function_type @ Token::FunctionType { .. } => {
+            let (params, returns) = signature(function_type)?;
+            Ok(format!(
+                "fn ({}){}",
+                params.join(", "),
+                returns.map(|t| format!(" {t}")).unwrap_or_default()
+            ))
+        }
+
+        box Token::TupleType(types) => Ok(format!(
+            "({})",
+            types
+                .into_iter()
+                .map(|t| translate(box t))
+                .collect::<Result<Vec<String>>>()?
+                .join(", ")
+        )),
+
+        box Token::Interpolation(segments) => {
+            info!("\tinterpolation from {source}");
+            let mut string = String::new();
+            for (_, segment) in segments {
+                match segment {
+                    Token::Literal(Literal::String(text)) => string.push_str(&escape(&text, '"')),
+                    value => {
+                        if let Some(reason) = unwritable(&value) {
+                            return Err(anyhow!(
+                                "{value} can't be written out in {source}, since {reason}"
+                            ));
+                        }
+
+                        string.push_str(&format!("${{{}}}", translate(box value)?))
+                    }
+                }
+            }
+
+            Ok(format!("\"{string}\""))
+        }
+
+        box function @ Token::Function { .. } => anonymous(function, None),
+
+        box Token::Lambda { .. } => Err(anyhow!(
+            "{source} doesn't say what types it takes, so it needs a type like fn(medium) -> \
+             medium, to be given to a parameter with one, or to be a step of a pipeline"
+        )),
+
+        box pipeline @ Token::Method(..) => {
+            info!("\tpipeline from {source}");
+            translate_pipeline(pipeline)
+        }
 
         box Token::Op(op) => Ok(format!("{}", translate_op(op)?)),
 
+        box Token::Unary(op) => translate_unary(op),
+
+        box Token::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            info!("\trange from {source}");
+            SCOPE.with(|s| s.borrow_mut().need(RANGE));
+            translate_range(start, end, inclusive)
+        }
+
         box Token::Ident(ident) => {
             info!("\tident from {source}");
+            let argument = SCOPE.with(|s| {
+                s.borrow()
+                    .arguments
+                    .iter()
+                    .rev()
+                    .find(|(param, _)| *param == *ident.fragment())
+                    .map(|(_, value)| value.clone())
+            });
+            if let Some(value) = argument {
+                return Ok(value);
+            }
+
+            if let Some(reference) = reference(&ident) {
+                return Ok(format!("(*{reference})"));
+            }
+
             if let Ok(transformed) = transform_literal(&ident) {
                 return Ok(transformed);
             }
-            Ok(format!("{ident}"))
+
+            let unaliased = SCOPE.with(|s| s.borrow().unaliased(&ident));
+            if let (Some(group), None) = (unaliased, binding(&ident)) {
+                return Err(anyhow!(
+                    "{ident} wasn't aliased, so it needs its group, like {group}/{ident}"
+                ));
+            }
+
+            let scope = SCOPE.with(|scope| {
+                let scope = scope.borrow();
+                match scope.variant(&ident) {
+                    Some(group) => Some(format!("{group}.{}", variant_name(&ident))),
+                    None => scope.group(&ident),
+                }
+            });
+
+            Ok(scope.unwrap_or_else(|| format!("{ident}")))
         }
 
-        box Token::None => {
-            info!("\tnone from {source}");
-            Ok(String::new())
+        box Token::Group { name, variants } => {
+            info!("\tgroup from {source}");
+            Ok(format!(
+                "enum {} {{\n{}\n}}",
+                type_name(&name),
+                variants
+                    .iter()
+                    .map(|v| variant_name(v))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            ))
         }
 
+        // Every aliased variant is already written out in full
+        box Token::Alias { .. } => Ok(String::new()),
+
+        box Token::Error(e) => Err(anyhow!("Can't translate a syntax error: {e}")),
+
         token => {
             info!("\t{:?} is unimplemented!", token);
             unimplemented!();
@@ -175,81 +775,1119 @@ fn translate(source: Box<Token>) -> Result<String> {
     }
 }
 
+/// The name an index assignment ends up changing: `field` in `field[y][x] = true`
+fn indexed(target: &Token) -> Option<String> {
+    match target {
+        Token::Index { value, .. } => indexed(value),
+        Token::Ident(name) => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// A value converted to its annotated type. V can't convert a whole array, so an array literal
+/// has each of its elements converted instead.
+fn translate_typed(value: Box<Token>, type_annotation: Option<Box<Token>>) -> Result<String> {
+    match (value, type_annotation) {
+        (box Token::Array(items), Some(box Token::ArrayType(t))) if items.is_empty() => {
+            Ok(format!("[]{}{{}}", translate(t)?))
+        }
+
+        (box Token::Array(items), Some(box Token::ArrayType(t))) => Ok(format!(
+            "[{}]",
+            items
+                .into_iter()
+                .map(|item| translate_typed(box item, Some(t.clone())))
+                .collect::<Result<Vec<String>>>()?
+                .join(", ")
+        )),
+
+        (_, Some(box Token::TupleType(_))) => Err(anyhow!(
+            "V can't keep a tuple in a variable, take it apart instead: [a, b] = ..."
+        )),
+
+        // Anything else that's meant to be an array already is one
+        (value, Some(box Token::ArrayType(_))) => translate(value),
+
+        (
+            box lambda @ Token::Lambda { .. },
+            Some(box function_type @ Token::FunctionType { .. }),
+        ) => typed_lambda(lambda, signature(function_type)?),
+
+        (
+            box function @ Token::Function { .. },
+            Some(box function_type @ Token::FunctionType { .. }),
+        ) => anonymous(function, Some(signature(function_type)?)),
+
+        // V can't convert a function either
+        (value, Some(box Token::FunctionType { .. })) => translate(value),
+
+        (value, Some(t)) => Ok(format!("{}({})", translate(t)?, translate(value)?)),
+
+        (value, None) => translate(value),
+    }
+}
+
+/// The values of a tuple, the way V returns and assigns several of them at once: `a, b`
+fn translate_tuple(values: Vec<Token>) -> Result<String> {
+    Ok(values
+        .into_iter()
+        .map(|value| translate(box value))
+        .collect::<Result<Vec<String>>>()?
+        .join(", "))
+}
+
+/// The object at the start of a pipeline, followed by each of its steps
+fn steps(pipeline: Token) -> Vec<Token> {
+    match pipeline {
+        Token::Method(box object, box step) => {
+            let mut steps = steps(object);
+            steps.push(step);
+            steps
+        }
+        object => vec![object],
+    }
+}
+
+/// A pipeline, which can name what a lambda in it is given in a line before its statement when
+/// it starts the statement, see `leading`
+fn translate_pipeline(pipeline: Token) -> Result<String> {
+    let mut lines = SCOPE.with(|s| s.borrow_mut().leading.take());
+    let translated = pipeline_with(pipeline, lines.as_mut());
+    SCOPE.with(|s| s.borrow_mut().leading = lines);
+
+    translated
+}
+
+fn pipeline_with(pipeline: Token, lines: Option<&mut Vec<String>>) -> Result<String> {
+    let mut steps = steps(pipeline);
+
+    // A `for` loop can count through a range of whole numbers that are written out, without it
+    // being built as an array. Anything else done with the range still needs that.
+    let element = element_name(SCOPE.with(|s| s.borrow().loops) + 1);
+    if let Some(header) = counting(&steps[0], &element) {
+        let range = match steps.remove(0) {
+            Token::Range {
+                start,
+                end,
+                inclusive,
+            } => translate_range(start, end, inclusive)?,
+            _ => unreachable!("only a range is counted through"),
+        };
+
+        let piped = pipe(range.clone(), range.clone(), steps, lines)?;
+        return match piped.strip_prefix(&format!("for {element} in {range} ")) {
+            Some(body) => Ok(format!("for {header} {body}")),
+            None => {
+                SCOPE.with(|s| s.borrow_mut().need(RANGE));
+                Ok(piped)
+            }
+        };
+    }
+
+    // An array is spread over the parameters of the first call, so `[x, y]; f` is `f(x, y)`
+    let object = steps.remove(0);
+    let args = match &object {
+        Token::Array(items) if !items.is_empty() => translate_tuple(items.clone())?,
+        _ => translate(box object.clone())?,
+    };
+    let value = translate(box object)?;
+
+    pipe(value, args, steps, lines)
+}
+
+/// How a `for` loop counts through a range of whole numbers that are written out, without
+/// building it with `dl_range`. V only counts down in a loop of its own. A range that includes its
+/// end is left to `dl_range`, since counting up to the largest value of its type would overflow.
+fn counting(range: &Token, element: &str) -> Option<String> {
+    let (start, end) = match range {
+        Token::Range {
+            box start,
+            box end,
+            inclusive: false,
+        } => (start, end),
+        _ => return None,
+    };
+    let (low, high) = match (start, end) {
+        (Token::Literal(low), Token::Literal(high)) => (whole_number(low)?, whole_number(high)?),
+        _ => return None,
+    };
+    let (start, end) = (translate(box start.clone()).ok()?, translate(box end.clone()).ok()?);
+
+    if low <= high {
+        Some(format!("{element} in {start} .. {end}"))
+    } else {
+        Some(format!(
+            "{element} := {start}; {element} > {end}; {element}--"
+        ))
+    }
+}
+
+/// `dl_range`, which the file has to `need`, unless it's only counted through
+fn translate_range(start: Box<Token>, end: Box<Token>, inclusive: bool) -> Result<String> {
+    if float(&start) || float(&end) {
+        return Err(anyhow!(
+            "Only whole numbers can make a range, unlike {}",
+            Token::Range {
+                start,
+                end,
+                inclusive
+            }
+        ));
+    }
+
+    Ok(format!(
+        "dl_range({}, {}, {inclusive})",
+        translate(start)?,
+        translate(end)?
+    ))
+}
+
+/// Goes through a pipeline one step at a time. An ordinary step is a call that takes the result
+/// so far as its first argument. `iter` turns an array or a range into an iterator, which the
+/// steps after it work through one element at a time:
+/// - `eachwill f` passes each element to `f`, and what `f` gives back on to the next step
+/// - `eachwillbecome f` does the same, but collects what `f` gives back into an array
+/// - an ordinary step is the same as `eachwill` with it
+///
+/// A named function or a lambda is called with the element, anything else is just evaluated for
+/// it. The steps for each element are put together into one expression, which V's `map` runs
+/// when it's collected, or a `for` loop runs otherwise. A lambda names what it's given in one of
+/// `lines` when it would work it out more than once.
+fn pipe(
+    value: String,
+    args: String,
+    steps: Vec<Token>,
+    lines: Option<&mut Vec<String>>,
+) -> Result<String> {
+    let loops = SCOPE.with(|s| s.borrow().loops);
+    let piped = pipe_steps(value, args, steps, lines);
+    SCOPE.with(|s| s.borrow_mut().loops = loops);
+
+    piped
+}
+
+fn pipe_steps(
+    mut value: String,
+    mut args: String,
+    steps: Vec<Token>,
+    mut lines: Option<&mut Vec<String>>,
+) -> Result<String> {
+    // What each element of the iterator becomes, when there is one, after whatever earlier
+    // steps did that the element doesn't carry along
+    let mut element: Option<(Vec<String>, String)> = None;
+    // What the element is called, and whether it's collected with `map` rather than looped over
+    let mut name = String::new();
+    let mut collected = false;
+
+    for (i, step) in steps.iter().enumerate() {
+        match step.clone() {
+            Token::Ident(iter) if *iter.fragment() == "iter" => {
+                if element.is_some() {
+                    return Err(anyhow!("iter was given something that's already iterating"));
+                }
+
+                // V's `map` calls the element `it`, but a `for` loop inside another one needs a
+                // name of its own
+                collected = steps[i + 1..]
+                    .iter()
+                    .take_while(
+                        |step| !matches!(step, Token::Ident(name) if *name.fragment() == "iter"),
+                    )
+                    .any(|step| {
+                        matches!(step, Token::MethodUnit(box Token::Ident(name), _)
+                            if *name.fragment() == "eachwillbecome")
+                    });
+                name = if collected {
+                    "it".to_string()
+                } else {
+                    element_name(SCOPE.with(|s| {
+                        let mut s = s.borrow_mut();
+                        s.loops += 1;
+                        s.loops
+                    }))
+                };
+                element = Some((Vec::new(), name.clone()));
+            }
+
+            Token::MethodUnit(box Token::Ident(step), box f)
+                if matches!(*step.fragment(), "eachwill" | "eachwillbecome") =>
+            {
+                let (mut done, mut each) = match element.take() {
+                    Some(element) => element,
+                    None => {
+                        return Err(anyhow!(
+                            "{step} goes through an iterator, so it needs \"; iter\" before it"
+                        ))
+                    }
+                };
+
+                // `map` has nowhere to name what a lambda is given, so the element is mapped to
+                // what it's become first
+                if collected && binds(&f) && !simple(&each) && done.is_empty() {
+                    value = format!("{value}.map({each})");
+                    each = name.clone();
+                }
+
+                let (next, took) = if collected {
+                    apply(f, &each, None)?
+                } else {
+                    apply(f, &each, Some(&mut done))?
+                };
+                if !took && each != name {
+                    done.push(each);
+                }
+
+                if *step.fragment() == "eachwill" {
+                    element = Some((done, next));
+                } else if done.is_empty() {
+                    value = format!("{value}.map({next})");
+                } else {
+                    return Err(anyhow!(
+                        "eachwillbecome can only collect what each element becomes, so it can't \
+                         come after an eachwill that throws the element away"
+                    ));
+                }
+            }
+
+            step => match element.take() {
+                Some((mut done, mut each)) => {
+                    if collected && binds(&step) && !simple(&each) && done.is_empty() {
+                        value = format!("{value}.map({each})");
+                        each = name.clone();
+                    }
+
+                    let next = if collected {
+                        call(step, &each, &each, None)?
+                    } else {
+                        call(step, &each, &each, Some(&mut done))?
+                    };
+                    element = Some((done, next));
+                }
+                None => value = call(step, &value, &args, lines.as_deref_mut())?,
+            },
+        }
+
+        args = value.clone();
+    }
+
+    Ok(match element {
+        Some((mut done, each)) if !done.is_empty() || each != name => {
+            done.push(each);
+            format!("for {name} in {value} {{\n{}\n}}", done.join("\n"))
+        }
+        _ => value,
+    })
+}
+
+/// What the element of the `for` loop `depth` loops in is called
+fn element_name(depth: usize) -> String {
+    match depth {
+        0 | 1 => "it".to_string(),
+        depth => format!("it{depth}"),
+    }
+}
+
+/// An ordinary step of a pipeline, which takes `args`. A lambda is given the whole `value`
+/// instead, since it isn't spread over parameters.
+fn call(step: Token, value: &str, args: &str, lines: Option<&mut Vec<String>>) -> Result<String> {
+    Ok(match shorthand(step) {
+        Token::Ident(f) => format!("{}({args})", translate(box Token::Ident(f))?),
+        Token::MethodUnit(box Token::Ident(f), box Token::Array(more)) => {
+            format!("{f}({args}, {})", translate_tuple(more)?)
+        }
+        Token::MethodUnit(box Token::Ident(f), box more) => {
+            format!("{f}({args}, {})", translate(box more)?)
+        }
+        step @ Token::Lambda { .. } => lambda(step, value, lines)?,
+        step => return Err(anyhow!("{step} can't be a step of a pipeline")),
+    })
+}
+
+fn signature(function_type: Token) -> Result<Signature> {
+    match function_type {
+        Token::FunctionType { params, returns } => Ok((
+            params
+                .into_iter()
+                .map(|t| translate(box t))
+                .collect::<Result<Vec<String>>>()?,
+            returns.map(translate).transpose()?,
+        )),
+        _ => Err(anyhow!(you_asked!("a function type"))),
+    }
+}
+
+/// A lambda written out as a V anonymous function, which takes its types from `signature`. A
+/// shorthand lambda's one value is called `it`.
+fn typed_lambda(lambda: Token, (types, returns): Signature) -> Result<String> {
+    let (params, body) = match lambda {
+        Token::Lambda { params, box body } => (params, body),
+        _ => return Err(anyhow!(you_asked!("a lambda"))),
+    };
+
+    let names = match params.len() {
+        0 => vec!["it".to_string()],
+        _ => params.iter().map(|param| param.to_string()).collect(),
+    };
+    if names.len() != types.len() {
+        return Err(anyhow!(
+            "The lambda takes {} values, but its type takes {}",
+            names.len(),
+            types.len()
+        ));
+    }
+
+    let declared: Vec<(String, bool)> = names.iter().map(|name| (name.clone(), false)).collect();
+    let (captured, references) = match SCOPE.with(|s| s.borrow().locals) {
+        Some(locals) => {
+            let (list, references, lines) = capture_list(captures(&declared, &body, locals));
+            if !lines.is_empty() {
+                return Err(anyhow!(
+                    "{} assigns to names from the function around it, which only a function \
+                     with a name of its own can do",
+                    Token::Lambda {
+                        params,
+                        body: box body
+                    }
+                ));
+            }
+
+            (list, references)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let inner = referencing(references, || {
+        scoped(declared, || match params.len() {
+            // `it` is already taken, so a loop in the lambda names its element differently
+            0 => {
+                SCOPE.with(|s| s.borrow_mut().loops += 1);
+                let piped = pipe("it".to_string(), "it".to_string(), steps(body), None);
+                SCOPE.with(|s| s.borrow_mut().loops -= 1);
+                piped
+            }
+            _ => translate(box body),
+        })
+    })?;
+
+    let (returns, keyword) = match returns {
+        Some(t) => (format!(" {t}"), "return "),
+        None => (String::new(), ""),
+    };
+
+    Ok(format!(
+        "fn {}({}){returns} {{\n{keyword}{inner}\n}}",
+        if captured.is_empty() {
+            String::new()
+        } else {
+            format!("[{}] ", captured.join(", "))
+        },
+        names
+            .iter()
+            .zip(types)
+            .map(|(name, t)| format!("{name} {t}"))
+            .collect::<Vec<String>>()
+            .join(", ")
+    ))
+}
+
+/// One `eachwill` or `eachwillbecome` step, applied to what the element has become so far. Also
+/// gives back whether the step took the element.
+fn apply(f: Token, element: &str, lines: Option<&mut Vec<String>>) -> Result<(String, bool)> {
+    match shorthand(f) {
+        Token::Ident(f) => Ok((
+            format!("{}({element})", translate(box Token::Ident(f))?),
+            true,
+        )),
+        f @ Token::Lambda { .. } => Ok((lambda(f, element, lines)?, true)),
+        f => Ok((translate(box f)?, false)),
+    }
+}
+
+/// A parenthesised pipeline that starts with a step, given where a step goes, is a shorthand
+/// lambda that pipes its value into that step: `eachwill (length; print)`
+fn shorthand(f: Token) -> Token {
+    match steps(f.clone()).first() {
+        Some(Token::Ident(_) | Token::MethodUnit(..)) if matches!(f, Token::Method(..)) => {
+            Token::Lambda {
+                params: Vec::new(),
+                body: box f,
+            }
+        }
+        _ => f,
+    }
+}
+
+/// Whether a step is a lambda that names the value it's given
+fn binds(f: &Token) -> bool {
+    matches!(f, Token::Lambda { params, .. } if !params.is_empty())
+}
+
+/// Whether a translated value is only a name or a number, so writing it out again doesn't work
+/// it out again
+fn simple(value: &str) -> bool {
+    value.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// A lambda given its one value. V's own anonymous functions need to be told every type, so the
+/// lambda is written out in place instead, with the value standing in for its parameter. A value
+/// that isn't `simple` is named in one of `lines` first, so it's still only worked out once.
+fn lambda(lambda: Token, value: &str, lines: Option<&mut Vec<String>>) -> Result<String> {
+    let source = lambda.to_string();
+    let (params, body) = match lambda {
+        Token::Lambda { params, box body } => (params, body),
+        _ => return Err(anyhow!(you_asked!("a lambda"))),
+    };
+
+    match &params[..] {
+        [] => pipe(value.to_string(), value.to_string(), steps(body), lines),
+
+        [param] => {
+            let value = match lines {
+                _ if simple(value) => value.to_string(),
+                Some(lines) => {
+                    let mut used = Vec::new();
+                    names(&body, &mut used, &mut Vec::new());
+                    if !used.contains(&param.to_string()) {
+                        lines.push(format!("_ = {value}"));
+                        value.to_string()
+                    } else {
+                        let name = SCOPE.with(|s| {
+                            let mut scope = s.borrow_mut();
+                            scope.named += 1;
+                            format!("dl_{param}{}", scope.named)
+                        });
+                        lines.push(format!("{name} := {value}"));
+                        declare(&name, false);
+                        name
+                    }
+                }
+                None => {
+                    return Err(anyhow!(
+                        "{source} would work out what it's given again each time it uses \
+                         {param} here. Name the value first, or start the statement with the \
+                         pipeline"
+                    ))
+                }
+            };
+
+            SCOPE.with(|s| s.borrow_mut().arguments.push((param.to_string(), value)));
+            let translated = translate(box body);
+            SCOPE.with(|s| s.borrow_mut().arguments.pop());
+
+            translated
+        }
+
+        _ => Err(anyhow!(
+            "A pipeline gives a lambda one value, but this one takes {}",
+            params.len()
+        )),
+    }
+}
+
 // rust macros :chef's kiss:
 fn translate_op(op: Op) -> Result<String> {
+    if let Op::Divide(_, divisor) | Op::Modulo(_, divisor) = &op {
+        if matches!(&**divisor, Token::Literal(lit) if whole_number(lit) == Some(0)) {
+            return Err(anyhow!("{} divides by zero", Token::Op(op)));
+        }
+    }
+
+    // `direction/North` is a variant, whether or not it was aliased
+    if let Op::Divide(group, variant) = &op {
+        if let (Token::Ident(group), Token::Ident(variant)) = (&**group, &**variant) {
+            let variants = SCOPE.with(|s| {
+                s.borrow()
+                    .groups
+                    .iter()
+                    .find(|(name, _)| name == group.fragment())
+                    .map(|(_, variants)| variants.clone())
+            });
+
+            match variants {
+                Some(variants) if variants.contains(&variant.to_string()) => {
+                    return Ok(format!("{}.{}", type_name(group), variant_name(variant)))
+                }
+                Some(_) => return Err(anyhow!("{group} doesn't have a variant {variant}")),
+                None => {}
+            }
+        }
+    }
+
+    if let Op::Modulo(t1, t2) = &op {
+        if float(t1) || float(t2) {
+            return Err(anyhow!(
+                "{} takes the remainder of a float, but only integers have one",
+                Token::Op(op)
+            ));
+        }
+    }
+
     ops!(op, [
         Add = former "+" latter,
         Subtract = former "-" latter,
+        Multiply = former "*" latter,
+        Divide = former "/" latter,
+        Modulo = former "%" latter,
         Eq = former "==" latter,
         Neq = former "!=" latter,
         Gt = former ">" latter,
         Lt = former "<" latter,
         Gte = former ">=" latter,
-        Lte = former "<=" latter
+        Lte = former "<=" latter,
+        And = former "&&" latter,
+        Or = former "||" latter
     ])
 }
 
+fn translate_unary(op: UnaryOp) -> Result<String> {
+    let (operator, operand) = match op {
+        UnaryOp::Not(t) => ("!", translate(t)?),
+        UnaryOp::Negate(t) => ("-", translate(t)?),
+    };
+
+    // `--1` would read as a decrement
+    if operand.starts_with('-') {
+        Ok(format!("({operator}({operand}))"))
+    } else {
+        Ok(format!("({operator}{operand})"))
+    }
+}
+
+/// V only runs the first branch that fits, like dolomite. It has no binding patterns though, so
+/// a binding arm becomes the `else` branch and declares its name itself. Aliased variants are
+/// patterns of their own.
+/// An arm that names the value it matches is given it again, so anything but a name is named
+/// first, before the statement. That can only happen when the match is the first thing its
+/// statement works out, see `leading`.
+/// A match that's a `statement` gives nothing back, so it doesn't need an arm for everything, and
+/// V is given an empty `else` instead.
+fn translate_match(
+    value: Box<Token>,
+    arms: Vec<(Pattern, Token)>,
+    statement: bool,
+) -> Result<String> {
+    let mut lines = SCOPE.with(|s| s.borrow_mut().leading.take());
+    let named = arms.iter().any(|(pattern, _)| {
+        matches!(pattern, Pattern::Binding(name)
+            if SCOPE.with(|s| s.borrow().variant(name).is_none()))
+    });
+
+    // V needs an `else` unless the arms go through every variant of a group
+    let catch_all = named
+        || arms
+            .iter()
+            .any(|(pattern, _)| matches!(pattern, Pattern::Wildcard(_)));
+    let variants = arms
+        .iter()
+        .filter_map(|(pattern, _)| match pattern {
+            Pattern::Binding(name) => Some(name.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    let missing = !catch_all && !SCOPE.with(|s| s.borrow().covers(&variants));
+    if missing && !statement {
+        return Err(anyhow!(
+            "The match on {value} has nothing to give back when none of its arms fit. Add an arm \
+             for everything else, like _: ..."
+        ));
+    }
+
+    let value = match (value, &mut lines) {
+        (box Token::Ident(name), _) => translate(box Token::Ident(name))?,
+        (value, _) if !named => translate(value)?,
+        (value, Some(lines)) => {
+            let subject = SCOPE.with(|s| {
+                let mut scope = s.borrow_mut();
+                scope.named += 1;
+                format!("dl_subject{}", scope.named)
+            });
+            lines.push(format!("{subject} := {}", translate(value)?));
+            declare(&subject, false);
+            subject
+        }
+        (value, None) => {
+            return Err(anyhow!(
+                "An arm of the match on {value} names what it's on, which would work it out \
+                 twice here. Name it first, like v = {value}, or match on it at the start of a \
+                 statement"
+            ))
+        }
+    };
+
+    let branches = arms
+        .into_iter()
+        .map(|(pattern, t)| {
+            let bound = match &pattern {
+                Pattern::Binding(name) if SCOPE.with(|s| s.borrow().variant(name).is_none()) => {
+                    if let Some(group) = SCOPE.with(|s| s.borrow().unaliased(name)) {
+                        return Err(anyhow!(
+                            "{name} wasn't aliased, so its arm would match anything. Alias it \
+                             first, like alias {group}/{name}"
+                        ));
+                    }
+
+                    vec![(name.to_string(), false)]
+                }
+                _ => Vec::new(),
+            };
+            let inner = scoped(bound, || translate(box t))?;
+            Ok(match pattern {
+                Pattern::Literal(lit) => {
+                    format!("{} {{\n{inner}\n}}", translate(box Token::Literal(lit))?)
+                }
+                Pattern::Range {
+                    start,
+                    end,
+                    inclusive,
+                } => {
+                    let (low, high) = range_bounds(&start, &end, inclusive)?;
+                    format!("{low}...{high} {{\n{inner}\n}}")
+                }
+                Pattern::Binding(name) if SCOPE.with(|s| s.borrow().variant(&name).is_some()) => {
+                    format!(".{} {{\n{inner}\n}}", variant_name(&name))
+                }
+                Pattern::Binding(name) if *name.fragment() != value => {
+                    format!("else {{\n{name} := {value}\n{inner}\n}}")
+                }
+                Pattern::Binding(_) | Pattern::Wildcard(_) => format!("else {{\n{inner}\n}}"),
+            })
+        })
+        .collect::<Result<Vec<String>>>()?;
+
+    SCOPE.with(|s| s.borrow_mut().leading = lines);
+    Ok(format!(
+        "match {value} {{\n{}{}\n}}",
+        branches.join("\n"),
+        if missing { "\nelse {}" } else { "" }
+    ))
+}
+
+/// The lowest and highest numbers a range pattern fits, for V's `low...high`
+fn range_bounds(start: &Literal, end: &Literal, inclusive: bool) -> Result<(i128, i128)> {
+    let (start, end) = match (whole_number(start), whole_number(end)) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return Err(anyhow!("Only whole numbers can make a range pattern")),
+    };
+
+    // The end is left out on whichever side of the start it's on
+    Ok(match (inclusive, start.cmp(&end)) {
+        (true, _) => (start.min(end), start.max(end)),
+        (false, Ordering::Less) => (start, end - 1),
+        (false, Ordering::Greater) => (end + 1, start),
+        (false, Ordering::Equal) => {
+            return Err(anyhow!(
+                "The range pattern {start}..{end} never fits anything"
+            ))
+        }
+    })
+}
+
+/// How many values a function with the return type `returns` gives back
+fn values(returns: Option<&Token>) -> usize {
+    match returns {
+        None => 0,
+        Some(Token::TupleType(types)) => types.len(),
+        Some(_) => 1,
+    }
+}
+
+/// Why a value in a string can't be written out, if the functions declared so far tell: it's a
+/// function or a group, or a call to a function that gives back nothing or a tuple. The types of
+/// other names aren't known, so a variable holding a tuple or a function gets through to V,
+/// which rejects it.
+fn unwritable(value: &Token) -> Option<String> {
+    let returns = |name: &str| SCOPE.with(|s| s.borrow().returns(name));
+
+    match value {
+        Token::Lambda { .. } | Token::Function { .. } => Some("it's a function".to_string()),
+
+        Token::Ident(name) if SCOPE.with(|s| s.borrow().group(name).is_some()) => {
+            Some(format!("{name} is a group"))
+        }
+
+        Token::Ident(name) if returns(name).is_some() => Some(format!("{name} is a function")),
+
+        Token::Method(_, box Token::MethodUnit(box Token::Ident(name), _))
+            if *name.fragment() == "eachwill" =>
+        {
+            Some("eachwill doesn't give a value back".to_string())
+        }
+
+        Token::MethodUnit(box Token::Ident(name), _)
+        | Token::Method(_, box Token::Ident(name))
+        | Token::Method(_, box Token::MethodUnit(box Token::Ident(name), _)) => {
+            match returns(name) {
+                Some(0) => Some(format!("{name} doesn't give a value back")),
+                Some(1) | None => None,
+                Some(_) => Some(format!("{name} gives back a tuple")),
+            }
+        }
+
+        // A variant, `direction/North`
+        Token::Op(Op::Divide(box Token::Ident(group), box Token::Ident(_)))
+            if SCOPE.with(|s| s.borrow().group(group).is_some()) =>
+        {
+            None
+        }
+
+        // Whatever an operator works on has to be a value as well
+        Token::Op(_) | Token::Unary(_) => value.children().into_iter().find_map(unwritable),
+
+        _ => None,
+    }
+}
+
+/// Whether a value is written as a float, or worked out from one. Nothing else about types is
+/// known here, so a float from a variable isn't caught, but V doesn't mix floats and integers in
+/// arithmetic anyway.
+fn float(token: &Token) -> bool {
+    match token {
+        Token::Literal(Literal::Vague(VagueLiteral::Float(_)))
+        | Token::Literal(Literal::Number(
+            StrictNumber::MediumFloat(_) | StrictNumber::LargeFloat(_),
+        )) => true,
+
+        Token::Unary(UnaryOp::Negate(t)) => float(t),
+
+        Token::Op(
+            op @ (Op::Add(..)
+            | Op::Subtract(..)
+            | Op::Multiply(..)
+            | Op::Divide(..)
+            | Op::Modulo(..)),
+        ) => {
+            let (t1, t2) = op.operands();
+            float(t1) || float(t2)
+        }
+
+        _ => false,
+    }
+}
+
+fn whole_number(lit: &Literal) -> Option<i128> {
+    match lit {
+        Literal::Vague(VagueLiteral::Integer(i)) => i.parse().ok(),
+        Literal::Number(n) => match *n {
+            StrictNumber::Byte(i) => Some(i.into()),
+            StrictNumber::ByteSigned(i) => Some(i.into()),
+            StrictNumber::Small(i) => Some(i.into()),
+            StrictNumber::SmallSigned(i) => Some(i.into()),
+            StrictNumber::Medium(i) => Some(i.into()),
+            StrictNumber::MediumSigned(i) => Some(i.into()),
+            StrictNumber::Large(i) => Some(i.into()),
+            StrictNumber::LargeSigned(i) => Some(i.into()),
+            StrictNumber::MediumFloat(_) | StrictNumber::LargeFloat(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// The name each of a function's parameters declares, whether it's `mut`, and its V type
+fn parameters(params: &[Token]) -> Result<Vec<(String, bool, String)>> {
+    params
+        .iter()
+        .map(|param| match param {
+            Token::Param {
+                type_annotation,
+                ident: box Token::Ident(name),
+                mutable,
+            } => Ok((
+                name.to_string(),
+                *mutable,
+                translate(type_annotation.clone())?,
+            )),
+            _ => Err(anyhow!(you_asked!("a parameter"))),
+        })
+        .collect()
+}
+
+/// The parameters of a V function: `mut x u32, y u32`
+fn parameter_list(params: &[(String, bool, String)]) -> String {
+    params
+        .iter()
+        .map(|(name, mutable, t)| format!("{}{name} {t}", if *mutable { "mut " } else { "" }))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// The types each of a function's parameters take, for the ones that take functions
+fn takes(params: &[Token]) -> Result<Vec<Option<Signature>>> {
+    params
+        .iter()
+        .map(|param| match param {
+            Token::Param {
+                type_annotation: box function_type @ Token::FunctionType { .. },
+                ..
+            } => Ok(Some(signature(function_type.clone())?)),
+            _ => Ok(None),
+        })
+        .collect()
+}
+
+/// Translates the body of a function, which returns what `returns` says. A closure's body
+/// reaches `references` through references.
+fn function_body(
+    params: Vec<(String, bool)>,
+    body: Box<Token>,
+    (tuple_return, function_return): (Option<usize>, Option<Signature>),
+    references: Vec<String>,
+) -> Result<String> {
+    let outer = SCOPE.with(|s| {
+        let mut s = s.borrow_mut();
+        let locals = s.locals.unwrap_or(s.bindings.len());
+        (
+            s.locals.replace(locals),
+            std::mem::replace(&mut s.tuple_return, tuple_return),
+            std::mem::replace(&mut s.function_return, function_return),
+        )
+    });
+    let inner = referencing(references, || scoped(params, || translate(body)));
+    SCOPE.with(|s| {
+        let mut s = s.borrow_mut();
+        s.locals = outer.0;
+        s.tuple_return = outer.1;
+        s.function_return = outer.2;
+    });
+
+    inner
+}
+
+/// A function with a block for its body, given as a value, written out as a V anonymous
+/// function. It gives back what `signature` says, when it's given where a function type is
+/// known, and nothing otherwise.
+fn anonymous(function: Token, signature: Option<Signature>) -> Result<String> {
+    let source = function.to_string();
+    let (args, body) = match function {
+        Token::Function { params, body } => (params.0, body),
+        _ => return Err(anyhow!(you_asked!("a function"))),
+    };
+
+    let typed = parameters(&args)?;
+    let types: Vec<String> = typed.iter().map(|(_, _, t)| t.clone()).collect();
+    let params: Vec<(String, bool)> = typed.iter().map(|(n, m, _)| (n.clone(), *m)).collect();
+    let returns = match signature {
+        Some((expected, _)) if expected != types => {
+            return Err(anyhow!(
+                "{source} takes {}, but its type takes {}",
+                types.join(", "),
+                expected.join(", ")
+            ))
+        }
+        Some((_, returns)) => returns,
+        None => None,
+    };
+
+    let body = match returns {
+        Some(_) => box implicit_return(*body),
+        None => body,
+    };
+    match mismatched_return(&body, returns.is_some()) {
+        Some(Token::Return(None)) => {
+            return Err(anyhow!(
+                "{source} has a plain return, but its type gives a value back"
+            ))
+        }
+        Some(mismatched) => {
+            return Err(anyhow!(
+                "{source} has {mismatched}, but its type doesn't give a value back"
+            ))
+        }
+        None => {}
+    }
+    if returns.is_some() && !always_returns(&body) {
+        return Err(anyhow!(
+            "{source} doesn't return a value on every path, but its type gives one back"
+        ));
+    }
+
+    let (captured, references) = match SCOPE.with(|s| s.borrow().locals) {
+        Some(locals) => {
+            let (list, references, lines) = capture_list(captures(&params, &body, locals));
+            if !lines.is_empty() {
+                return Err(anyhow!(
+                    "{source} assigns to names from the function around it, which only a \
+                     function with a name of its own can do"
+                ));
+            }
+
+            (list, references)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let fnargs = parameter_list(&typed);
+    let inner = function_body(params, body, (None, None), references)?;
+
+    Ok(format!(
+        "fn {}({fnargs}){} {{\n{inner}\n}}",
+        if captured.is_empty() {
+            String::new()
+        } else {
+            format!("[{}] ", captured.join(", "))
+        },
+        returns.map(|t| format!(" {t}")).unwrap_or_default()
+    ))
+}
+
+/// The arguments of a call to `f`. A lambda or a function takes its types from the parameter
+/// it's given to, when that takes a function.
+fn call_arguments(f: &str, args: Vec<Token>) -> Result<String> {
+    let takes = SCOPE.with(|s| {
+        s.borrow()
+            .functions
+            .iter()
+            .rev()
+            .find(|(name, _)| name == f)
+            .map(|(_, takes)| takes.clone())
+            .unwrap_or_default()
+    });
+
+    Ok(args
+        .into_iter()
+        .enumerate()
+        .map(|(i, arg)| match (arg, takes.get(i).cloned().flatten()) {
+            (lambda @ Token::Lambda { .. }, Some(signature)) => typed_lambda(lambda, signature),
+            (function @ Token::Function { .. }, signature) => anonymous(function, signature),
+            (arg, _) => translate(box arg),
+        })
+        .collect::<Result<Vec<String>>>()?
+        .join(", "))
+}
+
 fn gen_function(assignment: Box<Token>) -> Result<String> {
     info!("generating a function from {assignment}");
     match assignment {
         box Token::Assignment {
+            doc: _,
             mutable: _,
             type_annotation,
             ident,
             value,
         } => match value {
-            box Token::FnPair(args, body, after) => {
-                return Ok(format!(
-                    //TODO: Change for pub visibility levels
-                    r#"fn {name}({fnargs}) {ret} {{
-                        {inner}
-                    }}
+            box Token::Function { params: args, body } => {
+                // Only functions with a return type give their last expression back
+                let body = match type_annotation {
+                    Some(_) => box implicit_return(*body),
+                    None => body,
+                };
 
-                    {latter}
-                "#,
-                    inner = translate(body)?,
-                    name = match ident {
-                        box Token::Ident(s) => s,
-                        _ =>
-                            return Err(anyhow!(
-                                "I need a name for the function, or it wasn't valid."
-                            )),
-                    },
-                    fnargs = if args.0.is_empty() {
-                        String::new()
-                    } else {
-                        args.0
-                            .iter()
-                            .map(|id| match id {
-                                Token::Param {
-                                    type_annotation,
-                                    ident,
-                                    mutable,
-                                } => match (type_annotation, ident) {
-                                    (box Token::Ident(ty), box Token::Ident(param)) => {
-                                        format!(
-                                            "{}{param} {}",
-                                            if *mutable { "mut " } else { "" },
-                                            transform_literal(ty).unwrap()
-                                        )
-                                    }
-                                    _ => unimplemented!(),
-                                },
-                                _ => unimplemented!(),
-                            })
-                            .collect::<Vec<String>>()
-                            .join(", ")
-                    },
-                    ret = match type_annotation {
-                        Some(box Token::Ident(id)) => {
-                            transform_literal(&id)?
+                match mismatched_return(&body, type_annotation.is_some()) {
+                    Some(Token::Return(None)) => {
+                        return Err(anyhow!(
+                            "{ident} has a plain return, but it has a return type"
+                        ))
+                    }
+                    Some(mismatched) => {
+                        return Err(anyhow!(
+                            "{ident} has {mismatched}, but it has no return type"
+                        ))
+                    }
+                    None => {}
+                }
+                if type_annotation.is_some() && !always_returns(&body) {
+                    return Err(anyhow!(
+                        "{ident} doesn't return a value on every path, but it has a return type"
+                    ));
+                }
+
+                let tuple_return = match &type_annotation {
+                    Some(box Token::TupleType(types)) => Some(types.len()),
+                    _ => None,
+                };
+                let function_return = match &type_annotation {
+                    Some(box function_type @ Token::FunctionType { .. }) => {
+                        Some(signature(function_type.clone())?)
+                    }
+                    _ => None,
+                };
+                let name = match ident {
+                    box Token::Ident(s) => s.to_string(),
+                    _ => {
+                        return Err(anyhow!(
+                            "I need a name for the function, or it wasn't valid."
+                        ))
+                    }
+                };
+
+                let typed = parameters(&args.0)?;
+                let params: Vec<(String, bool)> =
+                    typed.iter().map(|(n, m, _)| (n.clone(), *m)).collect();
+                let fnargs = parameter_list(&typed);
+                let takes = takes(&args.0)?;
+                SCOPE.with(|s| {
+                    let mut scope = s.borrow_mut();
+                    scope.functions.push((name.clone(), takes));
+                    scope
+                        .returns
+                        .push((name.clone(), values(type_annotation.as_deref())));
+                });
+                let ret = match type_annotation {
+                    Some(ty) => translate(ty)?,
+
+                    None => String::new(),
+                };
+
+                // V only has functions at the top of a file, so one inside another is a closure
+                let (head, references, recursive) = match SCOPE.with(|s| s.borrow().locals) {
+                    Some(locals) => {
+                        let (mut captured, references, mut lines) =
+                            capture_list(captures(&params, &body, locals));
+
+                        // A closure can't see itself, so one that calls itself is declared
+                        // first, and reaches what it's assigned through a reference
+                        let mut used = Vec::new();
+                        names(&body, &mut used, &mut Vec::new());
+                        let recursive = used.contains(&name);
+                        if recursive {
+                            lines.push(format!(
+                                "mut {name} := fn ({fnargs}) {ret} {{\npanic(\"{name} was called \
+                                 before it was defined\")\n}}\n{name}_ref := &{name}"
+                            ));
+                            captured.push(format!("{name}_ref"));
+                            declare(&name, false);
                         }
 
-                        None => String::new(),
+                        let head = format!(
+                            "{}{name} {} fn {}",
+                            lines
+                                .iter()
+                                .map(|line| format!("{line}\n"))
+                                .collect::<String>(),
+                            if recursive { "=" } else { ":=" },
+                            if captured.is_empty() {
+                                String::new()
+                            } else {
+                                format!("[{}] ", captured.join(", "))
+                            }
+                        );
+
+                        (head, references, Some(recursive))
+                    }
+
+                    // Declared first, so the function can call itself
+                    None => {
+                        declare(&name, false);
+                        (format!("fn {name}"), Vec::new(), None)
+                    }
+                };
+
+                let inner =
+                    function_body(params, body, (tuple_return, function_return), references);
+                let inner = match recursive {
+                    Some(true) => format!("{name} := *{name}_ref\n{}", inner?),
+                    Some(false) => {
+                        declare(&name, false);
+                        inner?
+                    }
+                    None => inner?,
+                };
 
-                        _ => return Err(anyhow!("failed to parse return type for function")),
-                    },
-                    latter = translate(after)?
+                return Ok(format!(
+                    //TODO: Change for pub visibility levels
+                    r#"{head}({fnargs}) {ret} {{
+                        {inner}
+                    }}
+                "#
                 )
                 .lines()
                 .map(|s| s.trim().to_string())
@@ -266,3 +1904,171 @@ fn gen_function(assignment: Box<Token>) -> Result<String> {
         }
     }
 }
+
+/// The names a function inside another one uses from the functions around it, along with
+/// whether it assigns to them
+fn captures(params: &[(String, bool)], body: &Token, locals: usize) -> Vec<(String, bool)> {
+    let mut used = Vec::new();
+    let mut assigned = Vec::new();
+    names(body, &mut used, &mut assigned);
+
+    let enclosing = SCOPE.with(|s| s.borrow().bindings[locals..].concat());
+    let mut captured: Vec<(String, bool)> = Vec::new();
+    for used in used {
+        let local = enclosing.iter().any(|(name, _)| *name == used);
+        let param = params.iter().any(|(name, _)| *name == used);
+        if local && !param && !captured.iter().any(|(name, _)| *name == used) {
+            let mutable = assigned.contains(&used);
+            captured.push((used, mutable));
+        }
+    }
+
+    captured
+}
+
+/// How a V closure lists what it captures. V copies everything a closure lists, so a name the
+/// closure assigns to is listed as a `{name}_ref` reference instead, declared before the closure
+/// unless there's one already. Gives back the list, the names reached by reference, and the
+/// lines declaring the new references.
+fn capture_list(captured: Vec<(String, bool)>) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let outer = SCOPE.with(|s| s.borrow().references.clone());
+    let mut list = Vec::new();
+    let mut references = Vec::new();
+    let mut lines = Vec::new();
+    for (name, assigned) in captured {
+        if !assigned && !outer.contains(&name) {
+            list.push(name);
+            continue;
+        }
+
+        let reference = format!("{name}_ref");
+        if !outer.contains(&name) && binding(&reference).is_none() {
+            lines.push(format!("mut {reference} := &{name}"));
+            declare(&reference, true);
+        }
+
+        list.push(format!("{}{reference}", if assigned { "mut " } else { "" }));
+        references.push(name);
+    }
+
+    (list, references, lines)
+}
+
+/// Every name in `token`, and every name it assigns to, including through an index
+fn names(token: &Token, used: &mut Vec<String>, assigned: &mut Vec<String>) {
+    match token {
+        Token::Ident(name) => used.push(name.to_string()),
+        Token::Assignment {
+            ident: box Token::Array(targets),
+            ..
+        } => assigned.extend(targets.iter().map(|target| target.to_string())),
+        Token::Assignment { ident, .. } => assigned.extend(indexed(ident)),
+        _ => (),
+    }
+
+    for child in token.children() {
+        names(child, used, assigned);
+    }
+}
+
+/// Turns the value a block ends on into an explicit `return`, following `if`/`else` branches,
+/// since V functions have to say what they return.
+fn implicit_return(token: Token) -> Token {
+    match token {
+        Token::Block(mut statements) => {
+            if let Some(last) = statements.pop() {
+                statements.push(implicit_return(last));
+            }
+
+            Token::Block(statements)
+        }
+
+        Token::IfStmt {
+            cond,
+            block: box block,
+            else_branch: Some(box branch),
+        } => Token::IfStmt {
+            cond,
+            block: box implicit_return(block),
+            else_branch: Some(box implicit_return(branch)),
+        },
+
+        value @ (Token::Ident(_)
+        | Token::Literal(_)
+        | Token::Op(_)
+        | Token::Unary(_)
+        | Token::Method(..)
+        | Token::MethodUnit(..)
+        | Token::Array(_)
+        | Token::Index { .. }
+        | Token::Range { .. }
+        | Token::Match { .. }
+        | Token::Lambda { .. }
+        | Token::Function { .. }
+        | Token::Interpolation(_)) => Token::Return(Some(box value)),
+
+        statement => statement,
+    }
+}
+
+/// Whether running `token` always ends in a `return`, or never ends at all, so a function
+/// can't fall off the end of its body.
+fn always_returns(token: &Token) -> bool {
+    match token {
+        Token::Return(_) => true,
+
+        Token::Block(statements) => statements.iter().any(always_returns),
+
+        Token::IfStmt {
+            block,
+            else_branch: Some(branch),
+            ..
+        } => always_returns(block) && always_returns(branch),
+
+        // Only a `break` can get out of a loop without a condition
+        Token::Loop {
+            cond: None, block, ..
+        } => !breaks_out(block, &[]),
+
+        _ => false,
+    }
+}
+
+/// The first `return` in a function's body that doesn't fit whether the function `returns` a
+/// value, or `None` if they all do
+fn mismatched_return(token: &'t Token<'a>, returns: bool) -> Option<&'t Token<'a>> {
+    match token {
+        Token::Return(value) if value.is_some() != returns => Some(token),
+
+        // A function's returns are its own
+        Token::Function { .. } | Token::Lambda { .. } => None,
+
+        _ => token
+            .children()
+            .into_iter()
+            .find_map(|t| mismatched_return(t, returns)),
+    }
+}
+
+/// Whether a `break` inside a loop's body leaves that loop. `nested` holds the labels of the
+/// loops in between, which an unlabelled `break` or one naming them stops at instead.
+fn breaks_out(token: &Token, nested: &[Option<&str>]) -> bool {
+    match token {
+        Token::Break { label: None, .. } => nested.is_empty(),
+
+        Token::Break {
+            label: Some(label), ..
+        } => !nested.contains(&Some(*label.fragment())),
+
+        Token::Loop { label, .. } => {
+            let mut nested = nested.to_vec();
+            nested.push(label.map(|l| *l.fragment()));
+            token.children().into_iter().any(|t| breaks_out(t, &nested))
+        }
+
+        // A function's loops are its own
+        Token::Function { .. } | Token::Lambda { .. } => false,
+
+        _ => token.children().into_iter().any(|t| breaks_out(t, nested)),
+    }
+}
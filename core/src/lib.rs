@@ -12,8 +12,9 @@ pub mod parser;
 macro_rules! attempt {
     ($part: literal from $src: expr) => {
         if log::log_enabled!(log::Level::Info) {
-            if (&$src).trim().len() > 0 {
-                log::info!("ATTEMPT {} from {}", $part, &$src);
+            let preview = $crate::parser::lexer::preview($src);
+            if !preview.is_empty() {
+                log::info!("ATTEMPT {} from {}", $part, preview);
             }
         }
     };
@@ -23,8 +24,9 @@ macro_rules! attempt {
 macro_rules! got {
     ($part: literal from $src: expr) => {
         if log::log_enabled!(log::Level::Info) {
-            if (&$src).trim().len() > 0 {
-                log::info!("GOT {} from {}", $part, &$src);
+            let preview = $crate::parser::lexer::preview($src);
+            if !preview.is_empty() {
+                log::info!("GOT {} from {}", $part, preview);
             }
         }
     };
@@ -32,19 +34,554 @@ macro_rules! got {
 
 #[cfg(test)]
 mod tests {
+    use crate::compiler::translate::translate_file;
     use crate::compiler::Program;
-    use crate::parser::parse;
+    use crate::parser::{parse, Token};
 
     #[test]
     fn parse_test_1() {
         dolomite_logger::init();
         let src = std::fs::read_to_string("dl/parse-test-1.dl").unwrap();
-        let parsed = parse(&src);
+        let parsed = parse(&src).unwrap();
 
         let mut compiler = Program::new("parse_test_1").unwrap();
         compiler.compile(parsed).unwrap();
     }
 
+    #[test]
+    fn syntax_error_location() {
+        let errors = parse("x = 1\ny = (2 + 3").unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (2, 11));
+        assert_eq!(errors[0].expected, "\")\"");
+        assert_eq!(errors[0].found, "end of file");
+    }
+
+    #[test]
+    fn syntax_error_recovery() {
+        let src = "f = |medium x| {\n\ty = \n\tprint x\n\tz = ]\n}\nprint 1 +\nok = 1";
+        let errors = parse(src).unwrap_err();
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.line, e.column))
+                .collect::<Vec<(u32, usize)>>(),
+            vec![(2, 6), (4, 6), (6, 10)]
+        );
+    }
+
+    #[test]
+    fn braces_in_strings() {
+        let errors = parse("greet = || {\n\tprint \"}\\{\"\n\tprint ]\n}").unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (3, 8));
+        assert_eq!(errors[0].found, "\"]\"");
+    }
+
+    #[test]
+    fn operator_precedence() {
+        for (src, expected) in [
+            ("a - b - c", "a - b - c"),
+            ("(a - b) - c", "a - b - c"),
+            ("a - (b - c)", "a - (b - c)"),
+            ("a + b == c - d", "a + b == c - d"),
+            ("(a == b) + c", "(a == b) + c"),
+            ("a + b * c", "a + b * c"),
+            ("(a + b) * c", "(a + b) * c"),
+            ("a / b % c", "a / b % c"),
+            ("a / (b * c)", "a / (b * c)"),
+            ("!a == b", "!a == b"),
+            ("!(a == b)", "!(a == b)"),
+            ("-a * b", "-a * b"),
+            ("a - -b", "a - -b"),
+            ("a || b && c", "a || b && c"),
+            ("(a || b) && c", "(a || b) && c"),
+            ("a == b && c < d", "a == b && c < d"),
+            ("a + c..=b * d", "a + c..=b * d"),
+            ("(-a)[i]", "(-a)[i]"),
+            ("(a + b)[i][j]", "(a + b)[i][j]"),
+            ("(a..b)[i]", "(a..b)[i]"),
+        ] {
+            match parse(src).unwrap() {
+                Token::Block(statements) => assert_eq!(statements[0].to_string(), expected),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        // Each level is parsed once, so this doesn't take 4^40 tries
+        let src = format!(
+            "x = {}1{} + 2\nprint {}y{}\nz[{}0{}] = 1",
+            "(".repeat(40),
+            ")".repeat(40),
+            "[(".repeat(20),
+            ")]".repeat(20),
+            "-(".repeat(20),
+            ")".repeat(20)
+        );
+
+        parse(&src).unwrap();
+    }
+
+    #[test]
+    fn arithmetic_translation() {
+        let parsed = parse("x = -7 / 2 % 3 * 2mi\ny = 1.5l / 2.0").unwrap();
+
+        assert_eq!(
+            translate_file(box parsed).unwrap().trim(),
+            "x := (((-7 / 2) % 3) * i32(2))\ny := (f64(1.5) / 2.0)"
+        );
+
+        for src in ["x = 1.5 % 2", "x = 7 % (-2.0 * y)", "x = y / 0", "x = y % 0b"] {
+            assert!(translate_file(box parse(src).unwrap()).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn logical_translation() {
+        let parsed = parse("if !done && x > 0 || -y == 1 {\n\tprint x\n}").unwrap();
+
+        assert_eq!(
+            translate_file(box parsed).unwrap().trim(),
+            "if (((!done) && (x > 0)) || ((-y) == 1)) {\nprint(x)\n}"
+        );
+    }
+
+    #[test]
+    fn negative_arguments() {
+        let src = "print (-1)\nprint (-x)\nprint -x\nprint x-1\nprint [a, -b]\nx = y -1\nz = 10 -1";
+        let parsed = parse(src).unwrap();
+
+        assert_eq!(
+            translate_file(box parsed).unwrap().trim(),
+            "print(-1)\nprint((-x))\n(print - x)\nprint((x - 1))\nprint(a, (-b))\nx := (y - 1)\nz := (10 - 1)"
+        );
+    }
+
+    #[test]
+    fn booleans() {
+        let parsed = parse("boolean done = false\ntruth = trueish\nif !done || true {\n\tprint done\n}");
+
+        assert_eq!(
+            translate_file(box parsed.unwrap()).unwrap().trim(),
+            "done := bool(false)\ntruth := trueish\nif ((!done) || true) {\nprint(done)\n}"
+        );
+    }
+
+    #[test]
+    fn identifiers() {
+        match parse("_tmp2 = 1\nprint_bool = format_to_verse").unwrap() {
+            Token::Block(statements) => assert_eq!(
+                statements
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<String>>(),
+                vec![
+                    "Assignment: _tmp2 [unknown] = 1v",
+                    "Assignment: print_bool [unknown] = format_to_verse"
+                ]
+            ),
+            _ => unreachable!(),
+        }
+
+        let errors = parse("mut return = 1").unwrap_err();
+        assert_eq!((errors[0].line, errors[0].column), (1, 5));
+        assert_eq!(errors[0].expected, "an identifier");
+        assert_eq!(errors[0].found, "the keyword \"return\"");
+    }
+
+    #[test]
+    fn comments() {
+        let src = "// add_one = 0\n/// Adds one\n/// to x\nadd_one = |medium x| { /* } */\n\tprint x + 1 // }\n}\n/* block\n comment */\nadd_one 1";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("// Adds one\n// to x\nfn add_one(x u32)  {\nprint((x + 1))\n}"));
+        assert!(translated.trim().ends_with("add_one(1)"));
+
+        // Anywhere else, a doc comment is a plain comment
+        let src = "/// Prints one\nprint 1 /// and not 2\nx = [\n\t/// first\n\t1,\n\t2\n]\nmatch x {\n\t/// none\n\t0: print 0\n\t_: print 1\n\t/// done\n}\n/// the end";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("print(1)\nx := [1, 2]\nmatch x {"));
+        assert!(!translated.contains("//"));
+    }
+
+    #[test]
+    fn else_chains() {
+        let src = "if x == 1 {\n\tprint 1\n} else if x == 2 {\n\tif y {\n\t\tprint y\n\t} else {\n\t\tprint 2\n\t}\n} else {\n\tprint 3\n}";
+
+        assert_eq!(
+            translate_file(box parse(src).unwrap()).unwrap().trim(),
+            "if (x == 1) {\nprint(1)\n} else if (x == 2) {\nif y {\nprint(y)\n} else {\nprint(2)\n}\n} else {\nprint(3)\n}"
+        );
+    }
+
+    #[test]
+    fn loops() {
+        let src = "outer: loop {\n\twhile x < 10 {\n\t\tif x == 5 {\n\t\t\tbreak outer\n\t\t}\n\t\tcontinue\n\t}\n\tbreak\n}";
+
+        assert_eq!(
+            translate_file(box parse(src).unwrap()).unwrap().trim(),
+            "outer: for {\nfor (x < 10) {\nif (x == 5) {\nbreak outer\n}\ncontinue\n}\nbreak\n}"
+        );
+
+        let src = "break\nf = || {\n\tloop {\n\t\tg = || {\n\t\t\tcontinue\n\t\t}\n\t\tbreak inner\n\t}\n}";
+        let errors = parse(src).unwrap_err();
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.line, e.column))
+                .collect::<Vec<(u32, usize)>>(),
+            vec![(1, 1), (5, 4), (7, 9)]
+        );
+    }
+
+    #[test]
+    fn returns() {
+        let src = "medium pick = |boolean a| {\n\tif a {\n\t\treturn 1m\n\t} else if !a {\n\t\t2m\n\t} else {\n\t\t3m\n\t}\n}";
+
+        assert_eq!(
+            translate_file(box parse(src).unwrap()).unwrap().trim(),
+            "fn pick(a bool) u32 {\nif a {\nreturn u32(1)\n} else if (!a) {\nreturn u32(2)\n} else {\nreturn u32(3)\n}\n}"
+        );
+
+        for (src, ok) in [
+            ("medium half = |medium x| {\n\tif x > 1 {\n\t\treturn x / 2\n\t}\n}", false),
+            ("medium forever = || {\n\tloop {\n\t\tprint 1\n\t}\n}", true),
+            ("medium escape = || {\n\tloop {\n\t\tbreak\n\t}\n}", false),
+            ("medium inner = || {\n\tloop {\n\t\twhile x {\n\t\t\tbreak\n\t\t}\n\t}\n}", true),
+            ("printer = || {\n\tprint 1\n}", true),
+            ("medium bare = || {\n\treturn\n}", false),
+            ("valued = || {\n\treturn 1\n}", false),
+            ("early = || {\n\tif x {\n\t\treturn\n\t}\n}", true),
+        ] {
+            assert_eq!(translate_file(box parse(src).unwrap()).is_ok(), ok, "{src}");
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let src = "string verse = |medium n| {\n\tmatch n {\n\t\t0: \"none\"\n\t\t1m: \"one\"\n\n\t\ti: i + \" more\"\n\t}\n}\nprint match x + 1 { _: 2 }";
+
+        assert_eq!(
+            translate_file(box parse(src).unwrap()).unwrap().trim(),
+            "fn verse(n u32) string {\nmatch n {\n0 {\nreturn \"none\"\n}\nu32(1) {\nreturn \"one\"\n}\nelse {\ni := n\nreturn (i + \" more\")\n}\n}\n}\n\nprint(match (x + 1) {\nelse {\n2\n}\n})"
+        );
+
+        // An arm that names the value doesn't work it out again
+        let src = "medium next = |medium n| {\n\tmatch (read n) {\n\t\t0: 1\n\t\tt: t\n\t}\n}\nmedium x = match (next 2) {\n\tt: t + 1\n}";
+        assert!(translate_file(box parse(src).unwrap()).unwrap().contains("fn next(n u32) u32 {\ndl_subject1 := read(n)\nmatch dl_subject1 {\n0 {\nreturn 1\n}\nelse {\nt := dl_subject1\nreturn t\n}\n}\n}\n\ndl_subject2 := next(2)\nx := u32(match dl_subject2 {\nelse {\nt := dl_subject2\n(t + 1)\n}\n})"));
+        assert!(translate_file(box parse("print match (next 2) {\n\tt: t\n}").unwrap()).is_err());
+
+        // Only a match that doesn't give anything back can leave values out
+        let src = "match n {\n\t0: print 1\n}\ngroup direction = [North, East]\nalias direction/*\nmatch d {\n\tNorth: print 1\n\tEast: print 2\n}";
+        assert_eq!(
+            translate_file(box parse(src).unwrap()).unwrap().trim(),
+            "match n {\n0 {\nprint(1)\n}\nelse {}\n}\nenum Direction {\nnorth\neast\n}\n\nmatch d {\n.north {\nprint(1)\n}\n.east {\nprint(2)\n}\n}"
+        );
+        for src in [
+            "x = match n {\n\t0: 1\n}",
+            "group direction = [North, East]\nalias direction/*\nmedium f = |direction d| {\n\tmatch d {\n\t\tNorth: 1\n\t}\n}",
+        ] {
+            assert!(translate_file(box parse(src).unwrap()).is_err(), "{src}");
+        }
+
+        for (src, expected, found) in [
+            (
+                "x = match n {\n\ti: 1\n\t0: 2\n}",
+                "the last arm, since it matches anything",
+                "\"i\"",
+            ),
+            ("x = match n {\n}", "a pattern", "\"}\""),
+            ("x = match n {\n\t0: 1 1: 2\n}", "a line break", "\"1\""),
+        ] {
+            let errors = parse(src).unwrap_err();
+            assert_eq!(
+                (errors[0].expected.as_str(), errors[0].found.as_str()),
+                (expected, found),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn groups() {
+        let src = "group direction = [\n\tNorth, East,\n\tSouthWest\n]\nalias direction/*\ndirection flip = |direction d| {\n\tmatch d {\n\t\tNorth: SouthWest\n\t\tSouthWest: North\n\t\td: East\n\t}\n}";
+
+        assert_eq!(
+            translate_file(box parse(src).unwrap()).unwrap().trim(),
+            "enum Direction {\nnorth\neast\nsouth_west\n}\n\nfn flip(d Direction) Direction {\nmatch d {\n.north {\nreturn Direction.south_west\n}\n.south_west {\nreturn Direction.north\n}\nelse {\nreturn Direction.east\n}\n}\n}"
+        );
+
+        // A variant that isn't aliased is written with its group
+        let src = "group direction = [North, East]\nalias direction/North\nx = [direction/East, North]";
+        assert_eq!(
+            translate_file(box parse(src).unwrap()).unwrap().trim(),
+            "enum Direction {\nnorth\neast\n}\n\nx := [Direction.east, Direction.north]"
+        );
+        for src in ["x = East", "x = direction/South", "match d {\n\tEast: print 1\n}"] {
+            let src = format!("group direction = [North, East]\n{src}");
+            assert!(translate_file(box parse(&src).unwrap()).is_err(), "{src}");
+        }
+
+        // Only `North` is aliased, so `East` is a binding that leaves `North` nothing to match
+        let src = "group direction = [North, East]\nalias direction/North\nx = match d {\n\tEast: 1\n\tNorth: 2\n}\nalias compass/*\nalias direction/Up\nf = || {\n\tgroup inner = [A]\n\talias direction/*\n}";
+        let errors = parse(src).unwrap_err();
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.line, e.column, e.expected.as_str()))
+                .collect::<Vec<(u32, usize, &str)>>(),
+            vec![
+                (4, 2, "the last arm, since it matches anything"),
+                (7, 7, "a group"),
+                (8, 17, "a variant of direction"),
+                (10, 8, "to be at the top level of the file"),
+                (11, 8, "to be at the top level of the file")
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let src = "boolean first_is = |array[medium] input, medium value| {\n\tinput[0] == value\n}\nmut array[array[boolean]] field = [[true, false], []]\nfield[0][1] = !field[1][(i + 1) % 2]\nprint [field[0], 2]\nxs = [1, 2][0]";
+
+        assert_eq!(
+            translate_file(box parse(src).unwrap()).unwrap().trim(),
+            "fn first_is(input []u32, value u32) bool {\nreturn (input[0] == value)\n}\n\nmut field := [[bool(true), bool(false)], []bool{}]\nfield[0][1] = (!field[1][((i + 1) % 2)])\nprint(field[0], 2)\nxs := [1, 2][0]"
+        );
+
+        assert!(translate_file(box parse("xs = []").unwrap()).is_err());
+        assert!(parse("mut field[0] = 1").is_err());
+    }
+
+    #[test]
+    fn tuples() {
+        let src = "[medium, medium] swap = |medium x, medium y| {\n\t[y, x]\n}\n[medium, boolean] step = |boolean up, medium x| {\n\treturn match up {\n\t\ttrue: [x + 1, true]\n\t\t_: [x, false]\n\t}\n}\n[a, b] = swap [1, 2]\nmut [c, d] = [b, a]";
+
+        assert_eq!(
+            translate_file(box parse(src).unwrap()).unwrap().trim(),
+            "fn swap(x u32, y u32) (u32, u32) {\nreturn y, x\n}\n\nfn step(up bool, x u32) (u32, bool) {\nmatch up {\ntrue {\nreturn (x + 1), true\n}\nelse {\nreturn x, false\n}\n}\n}\n\na, b := swap(1, 2)\nmut c, mut d := b, a"
+        );
+
+        for src in [
+            "[medium, medium] one = || {\n\treturn [1]\n}",
+            "[medium, medium] pair = [1, 2]",
+        ] {
+            assert!(translate_file(box parse(src).unwrap()).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let src = "down = 99..0\nup = (x + 1)..=-x\nsize = match n {\n\t0..10: 1\n\t10..=99: 2\n\t0..-5: 3\n\t_: 4\n}";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("down := dl_range(99, 0, false)\nup := dl_range((x + 1), (-x), true)\nsize := match n {\n0...9 {\n1\n}\n10...99 {\n2\n}\n-4...0 {\n3\n}\nelse {\n4\n}\n}"));
+        assert_eq!(translated.matches("fn dl_range").count(), 1);
+        assert!(!translate_file(box parse("x = 1").unwrap()).unwrap().contains("dl_range"));
+
+        // Counting through a range of whole numbers doesn't build it
+        let src = "0..3; iter; eachwill print\n5..=1; iter; eachwill print";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+        assert!(translated.contains("for it in 0 .. 3 {\nprint(it)\n}\nfor it in dl_range(5, 1, true) {\nprint(it)\n}"));
+        let translated = translate_file(box parse("3..0; iter; eachwill print").unwrap()).unwrap();
+        assert!(translated.contains("for it := 3; it > 0; it-- {\nprint(it)\n}"));
+        assert!(!translated.contains("dl_range"));
+
+        for src in [
+            "x = 0.5..3",
+            "x = 0..(n / 2.0)",
+            "x = match n {\n\t1.5..2: 1\n\t_: 2\n}",
+            "x = match n {\n\t3..3: 1\n\t_: 2\n}",
+        ] {
+            assert!(translate_file(box parse(src).unwrap()).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn pipelines() {
+        let src = "string verse = |medium n| {\n\tn; format\n}\n99..0; iter; eachwill verse; eachwill print\ngrid = 0..2; iter; eachwillbecome (0..3; iter; eachwillbecome false)\n[1, 2]; add; print\n[1, 2, 3]; iter; eachwillbecome square; iter; eachwill print \"-\"";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("fn verse(n u32) string {\nreturn format(n)\n}\n\nfor it := 99; it > 0; it-- {\nprint(verse(it))\n}\ngrid := dl_range(0, 2, false).map(dl_range(0, 3, false).map(false))\nprint(add(1, 2))\nfor it in [1, 2, 3].map(square(it)) {\nprint(\"-\")\n}"));
+
+        for src in [
+            "x; eachwill print",
+            "x; iter; iter",
+            "x; iter; eachwill f; eachwill print \"-\"; eachwillbecome g",
+        ] {
+            assert!(translate_file(box parse(src).unwrap()).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn reassignment() {
+        let src = "mut lower = 0\nmut upper = 9\nloop {\n\tmid = (upper + lower) / 2\n\tif mid < 4 {\n\t\tlower = mid + 1\n\t} else {\n\t\tupper = mid - 1\n\t}\n}\nstep = |mut medium n| {\n\tn = n + 1\n}\nmut [a, b] = [1, 2]\n[a, b] = [b, a]\nmut grid = [[true]]\ngrid[0][0] = false";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("mut lower := 0\nmut upper := 9\nfor {\nmid := ((upper + lower) / 2)\nif (mid < 4) {\nlower = (mid + 1)\n} else {\nupper = (mid - 1)\n}\n}\nfn step(mut n u32)  {\nn = (n + 1)\n}\n\nmut a, mut b := 1, 2\na, b = b, a\nmut grid := [[true]]\ngrid[0][0] = false"));
+
+        for src in [
+            "x = 1\nx = 2",
+            "f = |medium n| {\n\tn = 1\n}",
+            "mut x = 1\nmut x = 2",
+            "mut a = 1\n[a, b] = [1, 2]",
+            "grid = [[true]]\ngrid[0][0] = false",
+            "x = 1\nf = || {\n\tx = 2\n}",
+        ] {
+            assert!(translate_file(box parse(src).unwrap()).is_err(), "{src}");
+        }
+
+        // Names declared inside a block are gone after it
+        assert!(translate_file(box parse("if true {\n\tx = 1\n}\nx = 2").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn closures() {
+        let src = "main = || {\n\tmut count = 0\n\ttotal = 5\n\tstep = |medium n| {\n\t\tcount = count + n\n\t\tprint total\n\t}\n\tnoop = |medium n| {\n\t\tprint n\n\t}\n\touter = || {\n\t\tinner = || {\n\t\t\tcount = 2\n\t\t}\n\t\tprint count\n\t}\n\tprint count\n}";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("fn main()  {\nmut count := 0\ntotal := 5\nmut count_ref := &count\nstep := fn [mut count_ref, total] (n u32)  {\n*count_ref = ((*count_ref) + n)\nprint(total)\n}\n\nnoop := fn (n u32)  {\nprint(n)\n}\n\nouter := fn [mut count_ref] ()  {\ninner := fn [mut count_ref] ()  {\n*count_ref = 2\n}\n\nprint((*count_ref))\n}\n\nprint(count)\n}"));
+
+        let src = "main = || {\n\tmut field = [false, false]\n\tmedium flip = |medium n| {\n\t\tfield[n] = true\n\t\treturn flip (n - 1)\n\t}\n}";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+        assert!(translated.contains("mut field_ref := &field\nmut flip := fn (n u32) u32 {\npanic(\"flip was called before it was defined\")\n}\nflip_ref := &flip\nflip = fn [mut field_ref, flip_ref] (n u32) u32 {\nflip := *flip_ref\n(*field_ref)[n] = true\nreturn flip((n - 1))\n}"));
+
+        let src = "main = || {\n\tx = 1\n\tf = || {\n\t\tx = 2\n\t}\n}";
+        assert!(translate_file(box parse(src).unwrap()).is_err());
+    }
+
+    #[test]
+    fn lambdas() {
+        let src = "5; |x| x * 2; print\n[1, 2, 3]; iter; eachwillbecome |x| x + 1\ngrid; iter; eachwill (iter; print_bool); eachwill print \"\\n\"\nrows; iter; eachwill |row| (row; length); eachwill print\nrows; iter; eachwill (length; print)";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("print((5 * 2))\n[1, 2, 3].map((it + 1))\nfor it in grid {\nfor it2 in it {\nprint_bool(it2)\n}\nprint(\"\\n\")\n}\nfor it in rows {\nprint(length(it))\n}\nfor it in rows {\nprint(length(it))\n}"));
+
+        // A lambda's body can be a call, but a pipeline after it goes on from what it's part of
+        let src = "5; |x| print x\nys = rows; iter; eachwillbecome |row| length row; sum";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("print(5)\nys := sum(rows.map(length(it)))"));
+
+        // What a lambda is given is only worked out once
+        let src = "xs; iter; eachwill f; eachwill |v| v + v\nys = xs; iter; eachwill f; eachwillbecome |v| v + v\ny = read n; |x| x * x";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("for it in xs {\ndl_v1 := f(it)\n(dl_v1 + dl_v1)\n}\nys := xs.map(f(it)).map((it + it))\ndl_x2 := read(n)\ny := (dl_x2 * dl_x2)"));
+
+        for src in ["add = |x| x + 1", "5; |x, y| x + y", "print (read n; |x| x * x)"] {
+            assert!(translate_file(box parse(src).unwrap()).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn function_types() {
+        let src = "fn(medium) -> medium double = |x| x * 2\nfn(medium) -> medium adder = |medium n| {\n\t|x| x + n\n}\nmedium twice = |fn(medium) -> medium f, medium x| {\n\tx; f; f\n}\nfn(array[medium]) -> array[medium] doubled = (iter; eachwillbecome double)\ntwice [double, 3]";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("double := fn (x u32) u32 {\nreturn (x * 2)\n}\nfn adder(n u32) fn (u32) u32 {\nreturn fn [n] (x u32) u32 {\nreturn (x + n)\n}\n}\n\nfn twice(f fn (u32) u32, x u32) u32 {\nreturn f(f(x))\n}\n\ndoubled := fn (it []u32) []u32 {\nreturn it.map(double(it))\n}\ntwice(double, 3)"));
+
+        // A function with a block for its body can be given as a value, and a lambda given to a
+        // parameter takes its types from it
+        let src = "fn(medium) -> medium same = || {\n\treturn |medium x| {\n\t\tx\n\t}\n}\nmedium twice = |fn(medium) -> medium f, medium x| {\n\tx; f; f\n}\nmain = || {\n\tk = 2\n\ttwice [|medium x| {\n\t\tx * k\n\t}, 3]\n\ttwice [|x| x + k, 3]\n}";
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains("fn same() fn (u32) u32 {\nreturn fn (x u32) u32 {\nreturn x\n}\n}"));
+        assert!(translated.contains("twice(fn [k] (x u32) u32 {\nreturn (x * k)\n}, 3)\ntwice(fn [k] (x u32) u32 {\nreturn (x + k)\n}, 3)"));
+
+        for src in [
+            "fn(medium, medium) -> medium add = |x| x",
+            "fn medium f = |x| x",
+            "f = |fn(medium -> medium g| {\n\tg\n}",
+            "medium twice = |fn(medium) -> medium f, medium x| {\n\tx; f; f\n}\ntwice [|large x| {\n\tx\n}, 3]",
+            "main = || {\n\tmut n = 0\n\tprint |medium x| {\n\t\tn = x\n\t}\n}",
+        ] {
+            assert!(
+                parse(src).map_or(true, |parsed| translate_file(box parsed).is_err()),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        let src = r#"print "one\ttwo\n\"three\" \\ \x41\u{1F41C} $5"
+character c = 'a'
+print '\n'
+print '\''
+print 'é'"#;
+        let translated = translate_file(box parse(src).unwrap()).unwrap();
+
+        assert!(translated.contains(
+            r#"print("one\ttwo\n\"three\" \\ A🐜 \$5")
+c := rune(`a`)
+print(`\n`)
+print(`'`)
+print(`é`)"#
+        ));
+
+        let bad_string = "a string with a bad escape or { }, or no closing \"";
+        let bad_char = "a character literal that isn't a single character";
+        for (src, found) in [
+            (r#"print "a\q""#, bad_string),
+            (r#"print "\x80""#, bad_string),
+            (r#"print "\u{110000}""#, bad_string),
+            ("print 'ab'", bad_char),
+            ("print ''", bad_char),
+        ] {
+            let errors = parse(src).unwrap_err();
+            assert_eq!(errors[0].column, 7, "{src}");
+            assert_eq!(errors[0].found, found, "{src}");
+        }
+    }
+
+    #[test]
+    fn interpolation() {
+        let functions = "medium count = |medium n| {\n\tn + 1\n}\nshout = |string s| {\n\tprint s\n}\n[medium, medium] pair = || {\n\t[1, 2]\n}\n";
+        let src = format!("{functions}n = 99\nprint \"{{n}} bottles of {{count n}} \\{{beer}} {{n + 1}}$\"");
+        let translated = translate_file(box parse(&src).unwrap()).unwrap();
+
+        assert!(translated.contains(r#"print("${n} bottles of ${count(n)} {beer} ${(n + 1)}\$")"#));
+
+        for (line, reason) in [
+            ("print \"{shout s}\"", "shout doesn't give a value back"),
+            ("print \"a {n; pair}\"", "pair gives back a tuple"),
+            ("print \"{pair}\"", "pair is a function"),
+            ("print \"{1 + (shout s)}\"", "shout doesn't give a value back"),
+            ("f = || {\n\tg = || {\n\t\tprint 1\n\t}\n\tprint \"{g 1}\"\n}", "g doesn't give a value back"),
+        ] {
+            let error = translate_file(box parse(&format!("{functions}{line}")).unwrap())
+                .unwrap_err()
+                .to_string();
+            assert!(
+                error.ends_with(&format!("since {reason}")),
+                "{line}: {error}"
+            );
+        }
+
+        let errors = parse(&format!("{functions}print \"{{1 +}}\"")).unwrap_err();
+        assert_eq!((errors[0].line, errors[0].column), (10, 12));
+        assert_eq!(errors[0].expected, "a value");
+    }
+
+    #[test]
+    fn long_file() {
+        let src = "mut x = 0\n".to_string()
+            + &"x = 1\nprint x + 1\nif x == 1 {\n\tprint x\n}\n".repeat(2000);
+        let parsed = parse(&src).unwrap();
+
+        translate_file(box parsed).unwrap();
+    }
+
     /*
     #[test]
     fn parse_test_2() {
@@ -1,12 +1,17 @@
 use std::fmt;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::parser::error::ParseError;
+use crate::parser::lexer::{Keyword, Punct, KEYWORDS, PUNCTUATION};
 use crate::parser::literals::{Literal, StrictNumber, VagueLiteral};
-use crate::parser::ops::Op;
-use crate::parser::{Token, TonsOfTokens};
+use crate::parser::ops::{Op, UnaryOp};
+use crate::parser::{Pattern, Token, TonsOfTokens};
 
 static INDENT: AtomicUsize = AtomicUsize::new(0);
-static DO_INDENT: AtomicBool = AtomicBool::new(false);
+
+fn tabs(indent: usize) -> String {
+    "\t".repeat(indent)
+}
 
 impl fmt::Display for VagueLiteral<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -35,47 +40,70 @@ impl fmt::Display for StrictNumber {
     }
 }
 
+/// Wraps an operand in parentheses only when leaving them out would change the tree: when it
+/// binds looser than its parent, or equally tightly on the right, since everything is left
+/// associative.
+fn operand(t: &Token, parent: usize, right: bool) -> String {
+    match t {
+        Token::Op(op) if op.precedence() < parent || (right && op.precedence() == parent) => {
+            format!("({t})")
+        }
+        _ => format!("{t}"),
+    }
+}
+
 impl fmt::Display for Op<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self {
-            &Op::Add(t1, t2) => {
-                write!(f, "({t1} + {t2})")
-            }
-
-            &Op::Subtract(t1, t2) => {
-                write!(f, "({t1} - {t2})")
-            }
-
-            &Op::Eq(t1, t2) => {
-                write!(f, "({t1} == {t2})")
-            }
+        let (t1, t2) = self.operands();
+        write!(
+            f,
+            "{} {} {}",
+            operand(t1, self.precedence(), false),
+            self.operator(),
+            operand(t2, self.precedence(), true)
+        )
+    }
+}
 
-            &Op::Neq(t1, t2) => {
-                write!(f, "({t1} != {t2})")
-            }
-            &Op::Gt(t1, t2) => {
-                write!(f, "({t1} > {t2})")
-            }
-            &Op::Lt(t1, t2) => {
-                write!(f, "({t1} < {t2})")
-            }
-            &Op::Gte(t1, t2) => {
-                write!(f, "({t1} >= {t2})")
-            }
-            &Op::Lte(t1, t2) => {
-                write!(f, "({t1} <= {t2})")
-            }
-        }
+impl fmt::Display for UnaryOp<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Nothing binds tighter than a prefix operator, so any `Op` underneath needs parentheses
+        write!(
+            f,
+            "{}{}",
+            self.operator(),
+            operand(self.operand(), usize::MAX, false)
+        )
     }
 }
 
+/// Text written back the way it would be in a string, where a `{` would start a value
+fn escaped(text: &str) -> String {
+    text.escape_debug().to_string().replace('{', "\\{")
+}
+
 impl fmt::Display for Literal<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
             &Literal::Number(num) => write!(f, "{num}"),
             &Literal::Vague(num) => write!(f, "{num}"),
             &Literal::Bool(b) => write!(f, "{b}"),
-            &Literal::String(s) => write!(f, "{s}"),
+            &Literal::String(s) => write!(f, "{}", escaped(s)),
+            &Literal::Char(c) => write!(f, "'{}'", c.escape_debug()),
+        }
+    }
+}
+
+impl fmt::Display for Pattern<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            &Pattern::Literal(literal) => write!(f, "{literal}"),
+            &Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(f, "{start}{}{end}", if *inclusive { "..=" } else { ".." }),
+            &Pattern::Binding(name) | &Pattern::Wildcard(name) => write!(f, "{name}"),
         }
     }
 }
@@ -98,130 +126,262 @@ impl fmt::Display for Token<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut out = Vec::new();
         let indent = INDENT.load(Ordering::Relaxed);
-        if DO_INDENT.load(Ordering::Relaxed) {
-            out.push((0..indent).into_iter().map(|_| '\t').collect::<String>());
-        }
 
         match &self {
             &Token::Ident(ident) => out.push(format!("{ident}")),
 
             &Token::Assignment {
+                doc,
                 mutable,
                 type_annotation,
                 ident,
                 value,
-            } => {
-                DO_INDENT.store(false, Ordering::Relaxed);
-                out.push(format!(
-                    "Assignment{mutable_fmt}: {ident} {type_annotation_fmt} = {value}",
-                    mutable_fmt = if *mutable { " (mut)" } else { "" },
-                    type_annotation_fmt = match type_annotation {
-                        None => "[unknown]".to_string(),
-                        Some(token) => format!("[{token}]"),
-                    }
-                ))
-            }
+            } => out.push(format!(
+                "{doc_fmt}Assignment{mutable_fmt}: {ident} {type_annotation_fmt} = {value}",
+                doc_fmt = doc
+                    .iter()
+                    .map(|line| format!("///{line}\n{}", tabs(indent)))
+                    .collect::<String>(),
+                mutable_fmt = if *mutable { " (mut)" } else { "" },
+                type_annotation_fmt = match type_annotation {
+                    None => "[unknown]".to_string(),
+                    Some(token) => format!("[{token}]"),
+                }
+            )),
 
             &Token::Param {
                 mutable,
                 type_annotation,
                 ident,
-            } => {
-                DO_INDENT.store(false, Ordering::Relaxed);
+            } => out.push(format!(
+                "{type_annotation} {ident} {mutable_fmt}",
+                mutable_fmt = if *mutable { " (mut)" } else { "" },
+            )),
+
+            // Only a value that can be indexed without parentheses is written without them
+            &Token::Index { value, index } => match &**value {
+                Token::Ident(_)
+                | Token::Literal(_)
+                | Token::Array(_)
+                | Token::Index { .. }
+                | Token::Match { .. }
+                | Token::Interpolation(_) => out.push(format!("{value}[{index}]")),
+                _ => out.push(format!("({value})[{index}]")),
+            },
+
+            &Token::ArrayType(t) => out.push(format!("array[{t}]")),
+
+            &Token::TupleType(types) => out.push(format!(
+                "[{}]",
+                types
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )),
+
+            &Token::FunctionType { params, returns } => {
                 out.push(format!(
-                    "{type_annotation} {ident} {mutable_fmt}",
-                    mutable_fmt = if *mutable { " (mut)" } else { "" },
-                ))
+                    "fn({})",
+                    params
+                        .iter()
+                        .map(|t| t.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ));
+
+                if let Some(returns) = returns {
+                    out.push(format!(" -> {returns}"));
+                }
             }
 
             &Token::Literal(literal) => out.push(format!("{literal}")),
 
+            &Token::Interpolation(segments) => out.push(format!(
+                "\"{}\"",
+                segments
+                    .iter()
+                    .map(|(_, t)| match t {
+                        Token::Literal(Literal::String(text)) => escaped(text),
+                        value => format!("{{{value}}}"),
+                    })
+                    .collect::<String>()
+            )),
+
             &Token::Op(op) => out.push(format!("{op}")),
 
-            &Token::IfStmt { cond } => out.push(format!("Cond: {cond}")),
+            &Token::Unary(op) => out.push(format!("{op}")),
 
-            &Token::Pair(p1, p2) => {
-                DO_INDENT.store(false, Ordering::Relaxed);
-                out.push(format!("<{p1}"));
-                DO_INDENT.store(true, Ordering::Relaxed);
-                if format!("{p2}").trim() == "" {
-                    out.push(format!(">"));
-                } else {
-                    out.push(format!(";\n{p2}>"));
-                }
-            }
+            &Token::Range {
+                start,
+                end,
+                inclusive,
+            } => out.push(format!(
+                "{start}{}{end}",
+                if *inclusive { "..=" } else { ".." }
+            )),
+
+            &Token::Block(statements) => out.push(
+                statements
+                    .iter()
+                    .map(|s| format!("{}<{s}>", tabs(indent)))
+                    .collect::<Vec<String>>()
+                    .join(";\n"),
+            ),
+
+            &Token::IfStmt {
+                cond,
+                block,
+                else_branch,
+            } => {
+                INDENT.store(indent + 1, Ordering::Relaxed);
+                out.push(format!("?Cond: {cond};\n{block}\n"));
+                INDENT.store(indent, Ordering::Relaxed);
+
+                match else_branch {
+                    // An else-if closes the whole chain itself
+                    Some(branch @ box Token::IfStmt { .. }) => {
+                        out.push(format!("{}?Else {branch}", tabs(indent)))
+                    }
 
-            &Token::CondPair(p1, p2, p3) => {
-                out.push((0..indent).into_iter().map(|_| '\t').collect::<String>());
-                DO_INDENT.store(false, Ordering::Relaxed);
-                out.push(format!("<<?{p1};\n"));
-                DO_INDENT.store(true, Ordering::Relaxed);
-                INDENT.store(INDENT.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
-                out.push(format!("{p2}\n"));
-                if format!("{p3}").trim() == "" {
-                    out.push((0..indent).into_iter().map(|_| '\t').collect::<String>());
-                    out.push(format!("?>"));
-                    DO_INDENT.store(false, Ordering::Relaxed);
-                } else {
-                    out.push(format!("?>\n"));
+                    Some(branch) => {
+                        INDENT.store(indent + 1, Ordering::Relaxed);
+                        out.push(format!("{}?Else;\n{branch}\n", tabs(indent)));
+                        INDENT.store(indent, Ordering::Relaxed);
+                        out.push(format!("{}?", tabs(indent)));
+                    }
+
+                    None => out.push(format!("{}?", tabs(indent))),
                 }
+            }
 
-                INDENT.store(INDENT.load(Ordering::Relaxed) - 1, Ordering::Relaxed);
-                out.push(format!("{p3}>"));
+            &Token::Loop { label, cond, block } => {
+                INDENT.store(indent + 1, Ordering::Relaxed);
+                out.push(format!(
+                    "∞{label_fmt}{cond_fmt};\n{block}\n",
+                    label_fmt = match label {
+                        Some(label) => format!(" {label}:"),
+                        None => String::new(),
+                    },
+                    cond_fmt = match cond {
+                        Some(cond) => format!(" while {cond}"),
+                        None => String::new(),
+                    }
+                ));
+                INDENT.store(indent, Ordering::Relaxed);
+                out.push(format!("{}∞", tabs(indent)));
             }
 
-            &Token::FnPair(p1, p2, p3) => {
-                out.push((0..indent).into_iter().map(|_| '\t').collect::<String>());
-                DO_INDENT.store(false, Ordering::Relaxed);
-                out.push(format!("<<λ{p1};\n"));
-                DO_INDENT.store(true, Ordering::Relaxed);
-                INDENT.store(INDENT.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
-                out.push(format!("{p2}\n"));
-                if format!("{p3}").trim() == "" {
-                    out.push((0..indent).into_iter().map(|_| '\t').collect::<String>());
-                    out.push(format!("λ>"));
-                    DO_INDENT.store(false, Ordering::Relaxed);
-                } else {
-                    out.push(format!("λ>\n"));
-                }
+            &Token::Break { label, .. } => match label {
+                Some(label) => out.push(format!("break {label}")),
+                None => out.push("break".to_string()),
+            },
+
+            &Token::Continue { label, .. } => match label {
+                Some(label) => out.push(format!("continue {label}")),
+                None => out.push("continue".to_string()),
+            },
 
-                INDENT.store(INDENT.load(Ordering::Relaxed) - 1, Ordering::Relaxed);
-                out.push(format!("{p3}>"));
+            &Token::Return(value) => match value {
+                Some(value) => out.push(format!("return {value}")),
+                None => out.push("return".to_string()),
+            },
+
+            &Token::Match { value, arms } => {
+                INDENT.store(indent + 1, Ordering::Relaxed);
+                out.push(format!(
+                    "≡ {value};\n{}\n",
+                    arms.iter()
+                        .map(|(pattern, t)| format!("{}<{pattern}: {t}>", tabs(indent + 1)))
+                        .collect::<Vec<String>>()
+                        .join(";\n")
+                ));
+                INDENT.store(indent, Ordering::Relaxed);
+                out.push(format!("{}≡", tabs(indent)));
             }
 
-            &Token::Method(operator, method) => {
-                DO_INDENT.store(false, Ordering::Relaxed);
-                out.push(format!("<{operator} THEN \n"));
-                DO_INDENT.store(true, Ordering::Relaxed);
-                out.push(format!("{method}>"));
+            &Token::Group { name, variants } => out.push(format!(
+                "Group {name} = [{}]",
+                variants
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )),
+
+            &Token::Alias { group, variant } => match variant {
+                Some(variant) => out.push(format!("Alias {group}/{variant}")),
+                None => out.push(format!("Alias {group}/*")),
+            },
+
+            &Token::Function { params, body } => {
+                INDENT.store(indent + 1, Ordering::Relaxed);
+                out.push(format!("λ{params};\n{body}\n"));
+                INDENT.store(indent, Ordering::Relaxed);
+                out.push(format!("{}λ", tabs(indent)));
             }
 
+            &Token::Lambda { params, body } if params.is_empty() => out.push(format!("λ({body})")),
+
+            &Token::Lambda { params, body } => out.push(format!(
+                "λ|{}| {body}",
+                params
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )),
+
+            &Token::Method(operator, method) => out.push(format!("<{operator} THEN {method}>")),
+
             &Token::Span(span, t) => out.push(format!(
                 "{t} @ {{{}, {}}}",
                 span.get_column(),
                 span.get_line_beginning()[0]
             )),
 
-            &Token::Array(objects) => {
-                DO_INDENT.store(false, Ordering::Relaxed);
-                out.push(format!(
-                    "[{}]",
-                    objects
-                        .iter()
-                        .map(|s| format!("{s}"))
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                ));
-            }
+            &Token::Array(objects) => out.push(format!(
+                "[{}]",
+                objects
+                    .iter()
+                    .map(|s| format!("{s}"))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )),
 
-            &Token::MethodUnit(method, args) => {
-                DO_INDENT.store(false, Ordering::Relaxed);
-                out.push(format!("λ {method}({args})"));
-            }
+            &Token::MethodUnit(method, args) => out.push(format!("λ {method}({args})")),
 
-            &Token::None => (),
+            &Token::Error(e) => out.push(format!("!{e}!")),
         }
 
         write!(f, "{}", out.join(""))
     }
 }
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (word, _) = KEYWORDS.iter().find(|(_, k)| k == self).unwrap();
+        write!(f, "{word}")
+    }
+}
+
+impl fmt::Display for Punct {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Punct::Negate {
+            return write!(f, "-");
+        }
+
+        let (symbol, _) = PUNCTUATION.iter().find(|(_, p)| p == self).unwrap();
+        write!(f, "{symbol}")
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: expected {}, found {}",
+            self.line, self.column, self.expected, self.found
+        )
+    }
+}
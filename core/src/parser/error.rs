@@ -0,0 +1,126 @@
+use std::cell::RefCell;
+
+use nom::error::{ContextError, ErrorKind};
+
+use crate::parser::lexer::{Kind, Lexeme};
+use crate::parser::literals::Literal;
+use crate::parser::util::{Lexemes, StrSpan};
+
+/// A syntax error, located in the source file: \
+/// `3:9: expected a value, found "+"`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub line: u32,
+    pub column: usize,
+    pub offset: usize,
+    pub expected: String,
+    pub found: String,
+}
+
+impl ParseError {
+    pub(super) fn new(i: Lexemes, expected: &str) -> Self {
+        track(Self::untracked(i, expected))
+    }
+
+    /// For problems found once the whole file has been parsed, which point at a span in the
+    /// tree instead of the lexemes.
+    pub(super) fn at(span: StrSpan, expected: &str) -> Self {
+        Self {
+            line: span.location_line(),
+            column: span.get_utf8_column(),
+            offset: span.location_offset(),
+            expected: expected.to_string(),
+            found: format!("\"{span}\""),
+        }
+    }
+
+    /// Errors straight out of nom's own combinators aren't worth reporting until something
+    /// gives them a proper name with `context`.
+    fn untracked(i: Lexemes, expected: &str) -> Self {
+        let Lexeme { kind, span } = &i[0];
+
+        Self {
+            line: span.location_line(),
+            column: span.get_utf8_column(),
+            offset: span.location_offset(),
+            expected: expected.to_string(),
+            found: found(kind, span),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+thread_local! {
+    /// The error that made it furthest into the file during the current parse.
+    /// Branches that fail deep inside a statement are otherwise thrown away as soon as a
+    /// shorter alternative (like a lone identifier) succeeds.
+    static FURTHEST: RefCell<Option<ParseError>> = const { RefCell::new(None) };
+}
+
+fn track(e: ParseError) -> ParseError {
+    FURTHEST.with(|furthest| {
+        let mut furthest = furthest.borrow_mut();
+        if !matches!(&*furthest, Some(f) if f.offset > e.offset) {
+            *furthest = Some(e.clone());
+        }
+    });
+
+    e
+}
+
+pub(super) fn take_furthest() -> Option<ParseError> {
+    FURTHEST.with(|furthest| furthest.borrow_mut().take())
+}
+
+/// Describes the lexeme an error points at, for the "found" half of the message.
+fn found(kind: &Kind, span: &StrSpan) -> String {
+    match kind {
+        Kind::Eof => "end of file".to_string(),
+        Kind::Newline => "a line break".to_string(),
+        Kind::Keyword(k) => format!("the keyword \"{k}\""),
+        Kind::Literal(Literal::String(_)) | Kind::Interpolation(_) => span.to_string(),
+        // Only a literal that didn't lex leaves its opening quote behind
+        Kind::Unknown('"') => "a string with a bad escape or { }, or no closing \"".to_string(),
+        Kind::Unknown('\'') => "a character literal that isn't a single character".to_string(),
+        _ => format!("\"{span}\""),
+    }
+}
+
+fn describe(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Eof => "end of file",
+        _ => "something else",
+    }
+}
+
+impl nom::error::ParseError<Lexemes<'t, 'a>> for ParseError {
+    fn from_error_kind(input: Lexemes<'t, 'a>, kind: ErrorKind) -> Self {
+        Self::untracked(input, describe(kind))
+    }
+
+    fn append(_: Lexemes<'t, 'a>, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Keeps whichever branch made it further into the file, since that's
+    /// almost always the one the user meant to write.
+    fn or(self, other: Self) -> Self {
+        if other.offset > self.offset {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl ContextError<Lexemes<'t, 'a>> for ParseError {
+    fn add_context(input: Lexemes<'t, 'a>, ctx: &'static str, mut other: Self) -> Self {
+        if other.offset == input[0].span.location_offset() {
+            other.expected = ctx.to_string();
+            return track(other);
+        }
+
+        other
+    }
+}
@@ -0,0 +1,346 @@
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_until, take_while, take_while1};
+use nom::character::complete::{anychar, char, satisfy};
+use nom::combinator::{consumed, map, not, recognize};
+use nom::error::ErrorKind;
+use nom::multi::many0;
+use nom::sequence::{pair, preceded, tuple};
+use nom::IResult;
+
+use crate::parser::literals::{boolean, interpolation, literal, Literal, Piece};
+use crate::parser::util::StrSpan;
+
+/// A single piece of source text along with what kind of piece it is
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lexeme<'a> {
+    pub kind: Kind<'a>,
+    pub span: StrSpan<'a>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Kind<'a> {
+    /// A reserved word from `KEYWORDS`: \
+    /// `if` \
+    /// `mut`
+    Keyword(Keyword),
+
+    /// Any other word, starting with a letter or `_` and carrying on with letters, digits and
+    /// `_`, such as: \
+    /// `helloworld` \
+    /// `print_bool`
+    Ident,
+
+    /// A literal value: \
+    /// `1` \
+    /// `2l` \
+    /// `"hello"`
+    Literal(Literal<'a>),
+
+    /// A string with values in it: \
+    /// `"{n} bottles"`
+    Interpolation(Vec<Piece<'a>>),
+
+    /// A symbol: \
+    /// `(` \
+    /// `==` \
+    /// `;`
+    Punct(Punct),
+
+    /// The text of a `///` doc comment, which documents the assignment below it. One that
+    /// doesn't start a line, or is inside `( )` or `[ ]`, is a plain comment and isn't kept.
+    DocComment(StrSpan<'a>),
+
+    /// A line break that ends a statement. Line breaks inside `( )` or `[ ]` are skipped.
+    Newline,
+
+    /// A character that can't start any lexeme
+    Unknown(char),
+
+    /// Always the last lexeme, so there's something to point at when the file ends too early
+    Eof,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Keyword {
+    If,
+    Else,
+    Mut,
+    Match,
+    Return,
+    Loop,
+    While,
+    Break,
+    Continue,
+    Alias,
+    Group,
+    Fn,
+}
+
+/// Words that can never be used as identifiers
+pub const KEYWORDS: &[(&str, Keyword)] = &[
+    ("if", Keyword::If),
+    ("else", Keyword::Else),
+    ("mut", Keyword::Mut),
+    ("match", Keyword::Match),
+    ("return", Keyword::Return),
+    ("loop", Keyword::Loop),
+    ("while", Keyword::While),
+    ("break", Keyword::Break),
+    ("continue", Keyword::Continue),
+    ("alias", Keyword::Alias),
+    ("group", Keyword::Group),
+    ("fn", Keyword::Fn),
+];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Punct {
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Pipe,
+    Comma,
+    Semicolon,
+    Colon,
+    Assign,
+    EqEq,
+    NotEq,
+    Lt,
+    Gt,
+    LtEq,
+    GtEq,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Bang,
+    AndAnd,
+    OrOr,
+    DotDot,
+    DotDotEq,
+    Arrow,
+    /// A `-` that negates the value right after it, rather than subtracting it. The lexer tells
+    /// the two apart, see `lex_span`.
+    Negate,
+}
+
+/// Longer symbols have to come before their prefixes, so `==` isn't read as `=` twice. The `-`
+/// of `Punct::Negate` isn't in here, since only the lexer knows which one a `-` is.
+pub const PUNCTUATION: &[(&str, Punct)] = &[
+    ("..=", Punct::DotDotEq),
+    ("..", Punct::DotDot),
+    ("&&", Punct::AndAnd),
+    ("||", Punct::OrOr),
+    ("==", Punct::EqEq),
+    ("!=", Punct::NotEq),
+    ("<=", Punct::LtEq),
+    (">=", Punct::GtEq),
+    ("->", Punct::Arrow),
+    ("!", Punct::Bang),
+    ("(", Punct::LParen),
+    (")", Punct::RParen),
+    ("{", Punct::LBrace),
+    ("}", Punct::RBrace),
+    ("[", Punct::LBracket),
+    ("]", Punct::RBracket),
+    ("|", Punct::Pipe),
+    (",", Punct::Comma),
+    (";", Punct::Semicolon),
+    (":", Punct::Colon),
+    ("=", Punct::Assign),
+    ("<", Punct::Lt),
+    (">", Punct::Gt),
+    ("+", Punct::Plus),
+    ("-", Punct::Minus),
+    ("*", Punct::Star),
+    ("/", Punct::Slash),
+    ("%", Punct::Percent),
+];
+
+/// Splits a whole file into lexemes. This never fails: anything unrecognizable becomes a
+/// `Kind::Unknown` for the parser to complain about, and the last lexeme is always `Kind::Eof`.
+pub fn lex(i: &'a str) -> Vec<Lexeme<'a>> {
+    lex_span(StrSpan::from(i))
+}
+
+/// Lexes part of a file, such as a value inside a string, keeping its place in the file
+pub fn lex_span(mut span: StrSpan<'a>) -> Vec<Lexeme<'a>> {
+    let mut lexemes: Vec<Lexeme> = Vec::new();
+    let mut nesting = Vec::new();
+
+    loop {
+        // The `/*` in `alias group/*` is a glob, not the start of a comment
+        let glob = span.starts_with("/*")
+            && matches!(
+                &lexemes[lexemes.len().saturating_sub(2)..],
+                [
+                    Lexeme {
+                        kind: Kind::Keyword(Keyword::Alias),
+                        ..
+                    },
+                    Lexeme {
+                        kind: Kind::Ident,
+                        ..
+                    }
+                ]
+            );
+
+        if !glob {
+            span = whitespace(span).unwrap().0;
+        }
+
+        if span.is_empty() {
+            lexemes.push(Lexeme {
+                kind: Kind::Eof,
+                span,
+            });
+
+            return lexemes;
+        }
+
+        // A `-` right after a value is subtraction, anywhere else it negates what follows it
+        let after_value = matches!(
+            lexemes.last().map(|l| &l.kind),
+            Some(
+                Kind::Ident
+                    | Kind::Literal(_)
+                    | Kind::Interpolation(_)
+                    | Kind::Punct(Punct::RParen | Punct::RBracket)
+            )
+        );
+        let negative = !after_value && !span.starts_with("->");
+
+        let line_start = matches!(
+            lexemes.last().map(|l| &l.kind),
+            None | Some(Kind::Newline | Kind::DocComment(_))
+        );
+
+        let (rest, (lexed, kind)) = consumed(|i| lexeme(i, negative))(span).unwrap();
+        span = rest;
+
+        match kind {
+            Kind::Punct(p @ (Punct::LParen | Punct::LBracket | Punct::LBrace)) => nesting.push(p),
+            Kind::Punct(p @ (Punct::RParen | Punct::RBracket)) => {
+                if nesting.last() == Some(&opener(p)) {
+                    nesting.pop();
+                }
+            }
+            // A `}` closes anything left unclosed inside its block, so one missing `)` doesn't
+            // swallow the line breaks of the rest of the file
+            Kind::Punct(Punct::RBrace) => {
+                while let Some(p) = nesting.pop() {
+                    if p == Punct::LBrace {
+                        break;
+                    }
+                }
+            }
+            Kind::Newline if matches!(nesting.last(), Some(Punct::LParen | Punct::LBracket)) => {
+                continue
+            }
+            Kind::DocComment(_)
+                if !line_start
+                    || matches!(nesting.last(), Some(Punct::LParen | Punct::LBracket)) =>
+            {
+                continue
+            }
+            _ => (),
+        }
+
+        lexemes.push(Lexeme { kind, span: lexed });
+    }
+}
+
+/// A short rendition of the upcoming lexemes for the `attempt!` and `got!` logs
+pub fn preview(i: &[Lexeme]) -> String {
+    i.iter()
+        .take_while(|l| l.kind != Kind::Eof)
+        .take(8)
+        .map(|l| match l.kind {
+            Kind::Newline => "\\n".to_string(),
+            _ => l.span.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn opener(closer: Punct) -> Punct {
+    match closer {
+        Punct::RParen => Punct::LParen,
+        Punct::RBracket => Punct::LBracket,
+        _ => Punct::LBrace,
+    }
+}
+
+/// Spaces, tabs and comments, which are all thrown away. Line breaks end statements and doc
+/// comments end up in the AST, so neither is whitespace.
+fn whitespace(i: StrSpan) -> IResult<StrSpan, StrSpan> {
+    recognize(many0(alt((
+        take_while1(|c| c == ' ' || c == '\t' || c == '\r'),
+        // `//` or `////`, but not `///`
+        recognize(tuple((
+            tag("//"),
+            not(pair(char('/'), not(char('/')))),
+            take_while(|c| c != '\n'),
+        ))),
+        recognize(tuple((tag("/*"), take_until("*/"), tag("*/")))),
+    ))))(i)
+}
+
+fn doc_comment(i: StrSpan) -> IResult<StrSpan, StrSpan> {
+    preceded(tag("///"), take_while(|c| c != '\n'))(i)
+}
+
+/// `negative` is whether a `-` here negates, rather than subtracts
+fn lexeme(i: StrSpan<'a>, negative: bool) -> IResult<StrSpan<'a>, Kind<'a>> {
+    alt((
+        map(char('\n'), |_| Kind::Newline),
+        map(doc_comment, Kind::DocComment),
+        map(boolean, |b| Kind::Literal(Literal::Bool(b))),
+        map(interpolation, Kind::Interpolation),
+        word,
+        |i: StrSpan<'a>| {
+            if !negative && i.starts_with('-') {
+                Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Digit)))
+            } else {
+                map(literal, Kind::Literal)(i)
+            }
+        },
+        |i: StrSpan<'a>| {
+            if negative {
+                map(char('-'), |_| Kind::Punct(Punct::Negate))(i)
+            } else {
+                Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Char)))
+            }
+        },
+        punctuation,
+        map(anychar, Kind::Unknown),
+    ))(i)
+}
+
+fn word(i: StrSpan) -> IResult<StrSpan, Kind> {
+    let (span, word) = recognize(pair(
+        satisfy(|c| c.is_alphabetic() || c == '_'),
+        take_while(|c: char| c.is_alphanumeric() || c == '_'),
+    ))(i)?;
+
+    return Ok((
+        span,
+        match KEYWORDS.iter().find(|(k, _)| *k == *word.fragment()) {
+            Some((_, keyword)) => Kind::Keyword(*keyword),
+            None => Kind::Ident,
+        },
+    ));
+}
+
+fn punctuation(i: StrSpan) -> IResult<StrSpan, Kind> {
+    for (symbol, punct) in PUNCTUATION {
+        if let Ok((span, _)) = tag::<_, _, nom::error::Error<StrSpan>>(*symbol)(i) {
+            return Ok((span, Kind::Punct(*punct)));
+        }
+    }
+
+    return Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Tag)));
+}
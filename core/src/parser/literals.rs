@@ -1,10 +1,10 @@
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_while, take_while1};
-use nom::character::complete::char;
-use nom::combinator::{map, opt};
-use nom::error::ErrorKind;
+use nom::bytes::complete::{tag, take_while1, take_while_m_n};
+use nom::character::complete::{char, digit1, satisfy};
+use nom::combinator::{consumed, map, map_opt, map_res, not, opt, recognize, value, verify};
+use nom::multi::{fold_many1, many0};
 use nom::number::complete::{double, float};
-use nom::sequence::{delimited, pair, preceded};
+use nom::sequence::{delimited, pair, preceded, terminated};
 use nom::IResult;
 
 use crate::parser::util::StrSpan;
@@ -35,20 +35,35 @@ pub enum Literal<'a> {
     Number(StrictNumber),
     Vague(VagueLiteral<'a>),
     Bool(bool),
-    String(StrSpan<'a>),
+    /// With its escapes already decoded
+    String(String),
+    Char(char),
+}
+
+/// A piece of a string with values in it: `"{n} bottles"`
+#[derive(Debug, PartialEq, Clone)]
+pub enum Piece<'a> {
+    /// Text with its escapes decoded, along with where it was written
+    Text(String, StrSpan<'a>),
+
+    /// The source of a value between `{ }`, which the parser reads once the lexer is done
+    Value(StrSpan<'a>),
 }
 
 macro_rules! def_strict_int {
     ($id: ident ($t: ident), $suffix: expr) => {
-        map(pair(int, tag($suffix)), |b| {
-            StrictNumber::$id(b.0.parse::<$t>().unwrap())
+        map_res(pair(int, tag($suffix)), |b| {
+            b.0.parse::<$t>().map(StrictNumber::$id)
         })
     };
 }
 
+/// Used by the lexer, so errors here only mean "this isn't a literal"
 pub fn literal(i: StrSpan) -> IResult<StrSpan, Literal> {
     alt((
-        map(string, |s| Literal::String(s)),
+        map(boolean, Literal::Bool),
+        map(string, Literal::String),
+        map(character, Literal::Char),
         map(strict_int, |n| Literal::Number(n)),
         map(int, |i| Literal::Vague(VagueLiteral::Integer(i))),
         map(strict_float, |n| Literal::Number(n)),
@@ -56,18 +71,20 @@ pub fn literal(i: StrSpan) -> IResult<StrSpan, Literal> {
     ))(i)
 }
 
-fn int(i: StrSpan) -> IResult<StrSpan, StrSpan> {
-    if i.contains(".") || i.trim() == "" {
-        Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Fail)))
-    } else if i.starts_with("-") {
-        preceded(opt(tag("-")), numbers)(i)
-    } else {
-        numbers(i)
-    }
+/// `true` or `false`, as long as it isn't the start of a longer word like `trueish`
+pub fn boolean(i: StrSpan) -> IResult<StrSpan, bool> {
+    terminated(
+        alt((value(true, tag("true")), value(false, tag("false")))),
+        not(satisfy(|c| c.is_alphanumeric() || c == '_')),
+    )(i)
 }
 
-fn numbers(i: StrSpan) -> IResult<StrSpan, StrSpan> {
-    take_while1(|c: char| c.is_digit(10))(i)
+/// An optionally negative run of digits that isn't the start of a float
+fn int(i: StrSpan) -> IResult<StrSpan, StrSpan> {
+    terminated(
+        recognize(preceded(opt(char('-')), digit1)),
+        not(pair(char('.'), digit1)),
+    )(i)
 }
 
 fn strict_float(i: StrSpan) -> IResult<StrSpan, StrictNumber> {
@@ -90,8 +107,91 @@ fn strict_int(i: StrSpan) -> IResult<StrSpan, StrictNumber> {
     ))(i)
 }
 
-//TODO: implement escaping
-fn string(i: StrSpan) -> IResult<StrSpan, StrSpan> {
-    let out = delimited(char('"'), take_while(|s| s != '"'), char('"'))(i);
-    return out;
+/// A double-quoted string without any values in it, which can run over several lines
+fn string(i: StrSpan) -> IResult<StrSpan, String> {
+    map_opt(pieces, |pieces| {
+        pieces
+            .into_iter()
+            .map(|piece| match piece {
+                Piece::Text(text, _) => Some(text),
+                Piece::Value(_) => None,
+            })
+            .collect()
+    })(i)
+}
+
+/// A double-quoted string with at least one value in it
+pub fn interpolation(i: StrSpan) -> IResult<StrSpan, Vec<Piece>> {
+    verify(pieces, |pieces: &[Piece]| {
+        pieces.iter().any(|piece| matches!(piece, Piece::Value(_)))
+    })(i)
+}
+
+/// A `{` starts a value, which the next `}` ends, so a `{` in the text has to be written `\{`
+fn pieces(i: StrSpan) -> IResult<StrSpan, Vec<Piece>> {
+    delimited(
+        char('"'),
+        many0(alt((
+            map(
+                consumed(fold_many1(
+                    alt((escape, satisfy(|c| c != '"' && c != '\\' && c != '{'))),
+                    String::new,
+                    |mut s, c| {
+                        s.push(c);
+                        s
+                    },
+                )),
+                |(span, text)| Piece::Text(text, span),
+            ),
+            map(
+                delimited(
+                    char('{'),
+                    take_while1(|c| c != '}' && c != '"' && c != '\n'),
+                    char('}'),
+                ),
+                Piece::Value,
+            ),
+        ))),
+        char('"'),
+    )(i)
+}
+
+/// A single-quoted character: `'a'` or `'\n'`
+fn character(i: StrSpan) -> IResult<StrSpan, char> {
+    delimited(
+        char('\''),
+        alt((escape, satisfy(|c| c != '\'' && c != '\\' && c != '\n'))),
+        char('\''),
+    )(i)
+}
+
+/// One of `\n`, `\t`, `\r`, `\0`, `\\`, `\"`, `\'` and `\{`, an ASCII character in hex like
+/// `\x41`, or any character by its code point in hex like `\u{1F41C}`
+fn escape(i: StrSpan) -> IResult<StrSpan, char> {
+    preceded(
+        char('\\'),
+        alt((
+            value('\n', char('n')),
+            value('\t', char('t')),
+            value('\r', char('r')),
+            value('\0', char('0')),
+            value('\\', char('\\')),
+            value('"', char('"')),
+            value('\'', char('\'')),
+            value('{', char('{')),
+            map_opt(preceded(char('x'), hex(2, 2)), |hex| {
+                u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(u8::is_ascii)
+                    .map(char::from)
+            }),
+            map_opt(delimited(tag("u{"), hex(1, 6), char('}')), |hex| {
+                u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+            }),
+        )),
+    )(i)
+}
+
+fn hex(min: usize, max: usize) -> impl FnMut(StrSpan<'a>) -> IResult<StrSpan<'a>, StrSpan<'a>> {
+    take_while_m_n(min, max, |c: char| c.is_ascii_hexdigit())
 }
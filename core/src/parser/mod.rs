@@ -1,28 +1,34 @@
 pub mod display;
+pub mod error;
+pub mod lexer;
 pub mod literals;
 pub mod ops;
 pub mod util;
 
 use log::info;
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_while1};
-use nom::character::complete::{char, space0, space1};
-use nom::combinator::{map, opt, rest};
-use nom::multi::separated_list1;
-use nom::sequence::{delimited, pair, tuple};
-use nom::IResult;
+use nom::combinator::{map, opt, verify};
+use nom::error::{context, ErrorKind};
+use nom::multi::{separated_list0, separated_list1};
+use nom::sequence::{delimited, preceded, tuple};
 use nom_locate::LocatedSpan;
 
-use crate::parser::literals::{literal, Literal};
-use crate::parser::ops::{assignment, ifstmt, method, method_def, method_unit, ops, Op};
-use crate::parser::util::StrSpan;
+use crate::parser::error::ParseError;
+use crate::parser::lexer::{lex, lex_span, Keyword, Kind, Lexeme, Punct};
+use crate::parser::literals::{Literal, Piece};
+use crate::parser::ops::{
+    alias, assignment, expression, group, ifstmt, jump, lambda, loop_stmt, match_expr, method,
+    method_def, ops, return_stmt, Op, UnaryOp,
+};
+use crate::parser::util::{keyword, punct, rest_of_file, Lexemes, ParseResult, StrSpan};
 use crate::{attempt, got};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token<'a> {
     /// An identifier such as: \
     /// `helloworld` \
-    /// `variable2`
+    /// `variable2` \
+    /// `print_bool`
     Ident(StrSpan<'a>),
 
     /// An assignment to a variable: \
@@ -30,12 +36,40 @@ pub enum Token<'a> {
     /// `mut b = 100` \
     /// `mut large population = 800l`
     Assignment {
+        /// The `///` doc comment lines right above the assignment, without their `///`
+        doc: Vec<StrSpan<'a>>,
         mutable: bool,
         type_annotation: Option<Box<Token<'a>>>,
         ident: Box<Token<'a>>,
         value: Box<Token<'a>>,
     },
 
+    /// An element of an array, read or assigned to: \
+    /// `input[mid]` \
+    /// `field[y][x]`
+    Index {
+        value: Box<Token<'a>>,
+        index: Box<Token<'a>>,
+    },
+
+    /// The type of an array, in a type annotation: \
+    /// `array[medium]` \
+    /// `array[array[boolean]]`
+    ArrayType(Box<Token<'a>>),
+
+    /// A fixed number of values of their own types, which a function can return together: \
+    /// `[medium, medium]`
+    TupleType(Vec<Token<'a>>),
+
+    /// The type of a function, from the types of its parameters and what it returns, if it
+    /// returns anything: \
+    /// `fn(medium) -> byte` \
+    /// `fn(string)`
+    FunctionType {
+        params: Vec<Token<'a>>,
+        returns: Option<Box<Token<'a>>>,
+    },
+
     /// A function parameter: \
     /// `small x` \
     /// `mut medium y` \
@@ -45,11 +79,15 @@ pub enum Token<'a> {
         ident: Box<Token<'a>>,
     },
 
-    /// An if statement's condition: \
-    /// `if x == 0` \
-    /// `if fecal == "funny"`
+    /// An if statement, with the block it guards and what to do otherwise: \
+    /// `if x == 0 { ... }` \
+    /// `if fecal == "funny" { ... } else { ... }` \
+    /// `if x < 0 { ... } else if x > 0 { ... }`
     IfStmt {
         cond: Box<Token<'a>>,
+        block: Box<Token<'a>>,
+        /// Either the `Token::Block` after `else`, or the `Token::IfStmt` after `else if`
+        else_branch: Option<Box<Token<'a>>>,
     },
 
     /// A literal value: \
@@ -64,18 +102,108 @@ pub enum Token<'a> {
     /// `30 == 30`
     Op(Op<'a>),
 
-    /// Connects two expressions together in a linked-list-ish format
-    Pair(Box<Token<'a>>, Box<Token<'a>>),
+    /// A prefix operation: \
+    /// `!lit` \
+    /// `-x`
+    Unary(UnaryOp<'a>),
+
+    /// Every value from `start` toward `end`, counting down when `end` is smaller. `end` itself
+    /// is only included with `..=`: \
+    /// `0..height` \
+    /// `99..0` \
+    /// `1..=10`
+    Range {
+        start: Box<Token<'a>>,
+        end: Box<Token<'a>>,
+        inclusive: bool,
+    },
 
-    /// Similar to `Token::Pair` but it contains a middle token for conditions or blocks.
-    CondPair(Box<Token<'a>>, Box<Token<'a>>, Box<Token<'a>>),
+    /// A loop, which runs forever or for as long as its condition holds, and can be labelled
+    /// for `break` and `continue` to refer to: \
+    /// `loop { ... }` \
+    /// `while x < 10 { ... }` \
+    /// `outer: loop { ... }`
+    Loop {
+        label: Option<StrSpan<'a>>,
+        cond: Option<Box<Token<'a>>>,
+        block: Box<Token<'a>>,
+    },
 
-    /// Similar to `Token::Pair` but it contains a middle token for a block's contents, while the first contains a list of parameters.
-    FnPair(TonsOfTokens<'a>, Box<Token<'a>>, Box<Token<'a>>),
+    /// Leaves the innermost loop, or the one with the given label: \
+    /// `break` \
+    /// `break outer`
+    Break {
+        keyword: StrSpan<'a>,
+        label: Option<StrSpan<'a>>,
+    },
 
-    /// An operation on an object: \
+    /// Skips to the next run of the innermost loop, or the one with the given label: \
+    /// `continue` \
+    /// `continue outer`
+    Continue {
+        keyword: StrSpan<'a>,
+        label: Option<StrSpan<'a>>,
+    },
+
+    /// Leaves the function, with or without a value: \
+    /// `return 0` \
+    /// `return`
+    Return(Option<Box<Token<'a>>>),
+
+    /// Evaluates to the value of the first arm whose pattern fits, without falling through to
+    /// the arms after it: \
+    /// `match n { 0: "none" \n 1: "one" \n i: i + " more" }`
+    Match {
+        value: Box<Token<'a>>,
+        arms: Vec<(Pattern<'a>, Token<'a>)>,
+    },
+
+    /// A named set of values, which can be used as a type. A variant is written with its group,
+    /// `direction/North`, unless it's aliased: \
+    /// `group direction = [North, East, South, West]`
+    Group {
+        name: StrSpan<'a>,
+        variants: Vec<StrSpan<'a>>,
+    },
+
+    /// Lets a group's variants be written without their group, either all of them or just one: \
+    /// `alias direction/*` \
+    /// `alias direction/North`
+    Alias {
+        group: StrSpan<'a>,
+        /// `None` for `*`
+        variant: Option<StrSpan<'a>>,
+    },
+
+    /// A sequence of statements, one per line, such as a whole file or the inside of `{ }`
+    Block(Vec<Stmt<'a>>),
+
+    /// A function definition, with its parameters and its body's `Token::Block`: \
+    /// `|medium x| { ... }` \
+    /// `|| { ... }`
+    Function {
+        params: TonsOfTokens<'a>,
+        body: Box<Token<'a>>,
+    },
+
+    /// A function without a name, whose body is a single expression: \
+    /// `|x| x + 1` \
+    /// `(iter; print_bool)`, a pipeline that starts with a step, which takes no parameters and
+    /// has its one value piped into it instead
+    Lambda {
+        params: Vec<StrSpan<'a>>,
+        body: Box<Token<'a>>,
+    },
+
+    /// A string with values in it, split into its text and its values, each along with where it
+    /// was written. The text is a `Literal::String`: \
+    /// `"{n} bottles of beer"`
+    Interpolation(Vec<(StrSpan<'a>, Token<'a>)>),
+
+    /// An operation on an object, which is the result of everything before the `;`: \
     /// `"hello"; print` \
-    /// `1; add_one`
+    /// `1; add_one` \
+    /// `99..0; iter; eachwill print`
     Method(Box<Token<'a>>, Box<Token<'a>>),
 
     /// Traditional function call: \
@@ -84,13 +212,44 @@ pub enum Token<'a> {
     MethodUnit(Box<Token<'a>>, Box<Token<'a>>),
     Span(LocatedSpan<&'a str>, Box<Token<'a>>),
     Array(Vec<Token<'a>>),
-    None,
+
+    /// Stands in for a line that failed to parse, so the rest of the file can still be read
+    Error(ParseError),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct TonsOfTokens<'a>(pub Vec<Token<'a>>);
 
-pub fn parse(i: &str) -> Token {
+/// What a `Token::Match` arm compares the value against
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern<'a> {
+    /// Fits a value equal to the literal: \
+    /// `0` \
+    /// `"yes"`
+    Literal(Literal<'a>),
+
+    /// Fits a whole number in the range, which counts the same way as a `Token::Range`: \
+    /// `1..10` \
+    /// `10..=1`
+    Range {
+        start: Literal<'a>,
+        end: Literal<'a>,
+        inclusive: bool,
+    },
+
+    /// Fits anything, and names it for the arm's value: \
+    /// `i`
+    Binding(StrSpan<'a>),
+
+    /// Fits anything: \
+    /// `_`
+    Wildcard(StrSpan<'a>),
+}
+
+/// A single line of a `Token::Block`. Statements share the same tree as expressions.
+pub type Stmt<'a> = Token<'a>;
+
+pub fn parse(i: &str) -> Result<Token, Vec<ParseError>> {
     info!(
         "Parsing file {}",
         if i.len() <= 30 {
@@ -101,7 +260,7 @@ pub fn parse(i: &str) -> Token {
                     .join(" -> "),
             )
         } else {
-            format!("{}...", &i[0..29])
+            format!("{}...", i.chars().take(29).collect::<String>())
                 .lines()
                 .filter(|s| s.trim().len() > 0)
                 .collect::<Vec<&str>>()
@@ -109,53 +268,404 @@ pub fn parse(i: &str) -> Token {
         }
     );
 
-    token(StrSpan::from(i)).unwrap().1
+    let lexemes = lex(i);
+    let parsed = match rest_of_file(&lexemes) {
+        Ok((_, parsed)) => parsed,
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => return Err(vec![e]),
+        Err(nom::Err::Incomplete(_)) => unreachable!("only complete parsers are used"),
+    };
+
+    let mut errors = Vec::new();
+    collect_errors(&parsed, &mut errors);
+    check_loops(&parsed, &mut Vec::new(), &mut errors);
+    check_top_level(&parsed, &mut errors);
+    check_aliases(&parsed, &mut errors);
+    check_matches(&parsed, &variants(&parsed), &mut errors);
+    errors.sort_by_key(|e| e.offset);
+
+    if errors.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(errors)
+    }
+}
+
+impl Token<'a> {
+    /// The tokens directly inside this one, in the order they were written
+    pub fn children(&self) -> Vec<&Token<'a>> {
+        match self {
+            Token::Assignment {
+                type_annotation,
+                ident,
+                value,
+                ..
+            } => type_annotation
+                .iter()
+                .map(|t| &**t)
+                .chain([&**ident, &**value])
+                .collect(),
+
+            Token::Param {
+                type_annotation,
+                ident,
+                ..
+            } => vec![type_annotation, ident],
+
+            Token::Index { value, index } => vec![value, index],
+
+            Token::ArrayType(t) => vec![t],
+
+            Token::TupleType(types) => types.iter().collect(),
+
+            Token::FunctionType { params, returns } => {
+                params.iter().chain(returns.iter().map(|t| &**t)).collect()
+            }
+
+            Token::IfStmt {
+                cond,
+                block,
+                else_branch,
+            } => [&**cond, &**block]
+                .into_iter()
+                .chain(else_branch.iter().map(|t| &**t))
+                .collect(),
+
+            Token::Loop { cond, block, .. } => {
+                cond.iter().map(|t| &**t).chain([&**block]).collect()
+            }
+
+            Token::Op(op) => {
+                let (t1, t2) = op.operands();
+                vec![t1, t2]
+            }
+
+            Token::Unary(op) => vec![op.operand()],
+
+            Token::Range { start, end, .. } => vec![start, end],
+
+            Token::Return(value) => value.iter().map(|t| &**t).collect(),
+
+            Token::Match { value, arms } => [&**value]
+                .into_iter()
+                .chain(arms.iter().map(|(_, t)| t))
+                .collect(),
+
+            Token::Method(t1, t2) | Token::MethodUnit(t1, t2) => vec![t1, t2],
+
+            Token::Block(statements) => statements.iter().collect(),
+
+            Token::Function { params, body } => params.0.iter().chain([&**body]).collect(),
+
+            Token::Lambda { body, .. } => vec![body],
+
+            Token::Interpolation(segments) => segments.iter().map(|(_, t)| t).collect(),
+
+            Token::Span(_, t) => vec![t],
+
+            Token::Array(objects) => objects.iter().collect(),
+
+            Token::Ident(_)
+            | Token::Literal(_)
+            | Token::Break { .. }
+            | Token::Continue { .. }
+            | Token::Group { .. }
+            | Token::Alias { .. }
+            | Token::Error(_) => Vec::new(),
+        }
+    }
+}
+
+fn collect_errors(token: &Token, errors: &mut Vec<ParseError>) {
+    match token {
+        Token::Error(e) => errors.push(e.clone()),
+        _ => token
+            .children()
+            .into_iter()
+            .for_each(|t| collect_errors(t, errors)),
+    }
+}
+
+/// Makes sure every `break` and `continue` sits inside a loop, and that any label they use
+/// belongs to one of the loops around them. A function body starts with no loops around it,
+/// since it can't break out of the loops it was defined in.
+fn check_loops(
+    token: &Token<'a>,
+    labels: &mut Vec<Option<&'a str>>,
+    errors: &mut Vec<ParseError>,
+) {
+    match token {
+        Token::Loop { label, .. } => {
+            labels.push(label.map(|l| *l.fragment()));
+            token
+                .children()
+                .into_iter()
+                .for_each(|t| check_loops(t, labels, errors));
+            labels.pop();
+        }
+
+        Token::Function { .. } | Token::Lambda { .. } => token
+            .children()
+            .into_iter()
+            .for_each(|t| check_loops(t, &mut Vec::new(), errors)),
+
+        Token::Break { keyword, label } | Token::Continue { keyword, label } => {
+            if labels.is_empty() {
+                errors.push(ParseError::at(*keyword, "to be inside a loop"));
+            } else if let Some(l) = label {
+                if !labels.contains(&Some(*l.fragment())) {
+                    errors.push(ParseError::at(*l, "the label of a loop around it"));
+                }
+            }
+        }
+
+        _ => token
+            .children()
+            .into_iter()
+            .for_each(|t| check_loops(t, labels, errors)),
+    }
+}
+
+/// Makes sure every `group` and `alias` is at the top level of the file, since V can only declare
+/// an enum there, and everything else only looks for aliases there
+fn check_top_level(file: &Token, errors: &mut Vec<ParseError>) {
+    for statement in file.children() {
+        statement
+            .children()
+            .into_iter()
+            .for_each(|t| check_nested(t, errors));
+    }
+}
+
+fn check_nested(token: &Token, errors: &mut Vec<ParseError>) {
+    match token {
+        Token::Group { name: span, .. } | Token::Alias { group: span, .. } => {
+            errors.push(ParseError::at(*span, "to be at the top level of the file"))
+        }
+        _ => token
+            .children()
+            .into_iter()
+            .for_each(|t| check_nested(t, errors)),
+    }
+}
+
+/// The groups declared at the top level of a file, which is the only place they can go
+pub fn groups(file: &'t Token<'a>) -> Vec<(StrSpan<'a>, &'t [StrSpan<'a>])> {
+    match file {
+        Token::Block(statements) => statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Token::Group { name, variants } => Some((*name, &variants[..])),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The functions declared at the top level of a file, along with their return types
+pub fn functions(file: &'t Token<'a>) -> Vec<(StrSpan<'a>, Option<&'t Token<'a>>)> {
+    match file {
+        Token::Block(statements) => statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Token::Assignment {
+                    type_annotation,
+                    ident: box Token::Ident(name),
+                    value: box Token::Function { .. },
+                    ..
+                } => Some((*name, type_annotation.as_deref())),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Every variant that the `alias` statements at the top level of a file let it write without its
+/// group, along with that group. Aliases of groups or variants that don't exist are left out.
+pub fn variants(file: &Token<'a>) -> Vec<(StrSpan<'a>, StrSpan<'a>)> {
+    let groups = groups(file);
+    let statements = match file {
+        Token::Block(statements) => statements,
+        _ => return Vec::new(),
+    };
+
+    statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Token::Alias { group, variant } => groups
+                .iter()
+                .find(|(name, _)| name.fragment() == group.fragment())
+                .map(|(name, variants)| (*name, *variants, variant)),
+            _ => None,
+        })
+        .flat_map(|(name, variants, variant)| {
+            variants
+                .iter()
+                .filter(move |v| variant.map_or(true, |only| only.fragment() == v.fragment()))
+                .map(move |v| (name, *v))
+        })
+        .collect()
+}
+
+/// Makes sure every `alias` names a group, and a variant that group has
+fn check_aliases(file: &Token, errors: &mut Vec<ParseError>) {
+    let groups = groups(file);
+    let statements = match file {
+        Token::Block(statements) => statements,
+        _ => return,
+    };
+
+    for statement in statements {
+        if let Token::Alias { group, variant } = statement {
+            let variants = groups
+                .iter()
+                .find(|(name, _)| name.fragment() == group.fragment())
+                .map(|(_, variants)| variants);
+
+            match (variants, variant) {
+                (None, _) => errors.push(ParseError::at(*group, "a group")),
+                (Some(variants), Some(v))
+                    if !variants
+                        .iter()
+                        .any(|other| other.fragment() == v.fragment()) =>
+                {
+                    errors.push(ParseError::at(*v, &format!("a variant of {group}")))
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Makes sure no `match` arm comes after one that matches anything, since it could never be
+/// picked. A name that an `alias` brought in is a variant rather than a binding, so it only
+/// matches itself.
+fn check_matches(token: &Token, variants: &[(StrSpan, StrSpan)], errors: &mut Vec<ParseError>) {
+    if let Token::Match { arms, .. } = token {
+        // The last arm can match anything, there's nothing after it to miss out
+        let catch_all = arms
+            .iter()
+            .take(arms.len().saturating_sub(1))
+            .find_map(|(pattern, _)| match pattern {
+                Pattern::Binding(name)
+                    if !variants
+                        .iter()
+                        .any(|(_, v)| v.fragment() == name.fragment()) =>
+                {
+                    Some(name)
+                }
+                Pattern::Wildcard(name) => Some(name),
+                _ => None,
+            });
+
+        if let Some(name) = catch_all {
+            errors.push(ParseError::at(
+                *name,
+                "the last arm, since it matches anything",
+            ));
+        }
+    }
+
+    token
+        .children()
+        .into_iter()
+        .for_each(|t| check_matches(t, variants, errors));
 }
 
-pub(self) fn paren(i: StrSpan) -> IResult<StrSpan, Token> {
+pub(self) fn paren(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("paren" from i);
-    let (span, parsed) = delimited(pair(char('('), space0), token, pair(space0, char(')')))(i)?;
+    let (span, parsed) = delimited(
+        punct(Punct::LParen),
+        context("an expression", token),
+        punct(Punct::RParen),
+    )(i)?;
     got!("paren" from i);
 
-    return Ok((span, parsed));
+    if !starts_with_step(&parsed) {
+        return Ok((span, parsed));
+    }
+
+    return Ok((
+        span,
+        Token::Lambda {
+            params: Vec::new(),
+            body: box parsed,
+        },
+    ));
 }
 
-pub(self) fn singleton(i: StrSpan) -> IResult<StrSpan, Token> {
+/// Whether a pipeline starts with one of the iterator steps, which need a value piped into them
+fn starts_with_step(token: &Token) -> bool {
+    match token {
+        Token::Method(object, _) => starts_with_step(object),
+        Token::Ident(name) => *name.fragment() == "iter",
+        Token::MethodUnit(box Token::Ident(name), _) => {
+            matches!(*name.fragment(), "eachwill" | "eachwillbecome")
+        }
+        _ => false,
+    }
+}
+
+pub(self) fn singleton(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("singleton" from i);
-    let (span, parsed) = alt((
-        paren,
-        map(ident, Token::Ident),
-        map(literal, Token::Literal),
-    ))(i)?;
+    let (span, parsed) = context(
+        "a value",
+        alt((
+            paren,
+            match_expr,
+            array,
+            map(ident, Token::Ident),
+            map(literal, Token::Literal),
+            interpolation,
+        )),
+    )(i)?;
+    let (span, parsed) = indexes(i, span, parsed)?;
 
     got!("singleton" from i);
 
     return Ok((span, parsed));
 }
 
-pub(self) fn token(i: StrSpan) -> IResult<StrSpan, Token> {
+/// One step of a pipeline, which is anything but another pipeline, so the `;` in
+/// `eachwill print "\n"; f` hands the result of the whole call on to `f`
+pub(self) fn stage(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("stage" from i);
+    let (span, parsed) = context("a value", alt((lambda, ops)))(i)?;
+
+    got!("stage" from i);
+
+    return Ok((span, parsed));
+}
+
+pub(self) fn token(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("token" from i);
-    let (span, parsed) = alt((
-        method_def,
-        ifstmt,
-        assignment,
-        method,
-        ops,
-        method_unit,
-        array,
-        map(ident, Token::Ident),
-        map(literal, Token::Literal),
-        map(rest, |_| Token::None),
-    ))(i)?;
+    let (span, parsed) = context(
+        "a statement or expression",
+        alt((
+            method_def,
+            ifstmt,
+            loop_stmt,
+            jump,
+            return_stmt,
+            group,
+            alias,
+            assignment,
+            method,
+        )),
+    )(i)?;
 
     got!("token" from i);
 
     return Ok((span, parsed));
 }
 
-pub(self) fn param(i: StrSpan) -> IResult<StrSpan, Token> {
+pub(self) fn param(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("param" from i);
-    let (span, parsed) = tuple((opt(pair(tag("mut"), space1)), ident, space1, ident))(i)?;
+    let (span, parsed) = context(
+        "a parameter",
+        tuple((opt(keyword(Keyword::Mut)), type_annotation, ident)),
+    )(i)?;
 
     got!("param" from i);
 
@@ -163,27 +673,179 @@ pub(self) fn param(i: StrSpan) -> IResult<StrSpan, Token> {
         span,
         Token::Param {
             mutable: parsed.0.is_some(),
-            ident: box Token::Ident(parsed.3),
-            type_annotation: box Token::Ident(parsed.1),
+            ident: box Token::Ident(parsed.2),
+            type_annotation: box parsed.1,
         },
     ));
 }
 
-fn ident(i: StrSpan) -> IResult<StrSpan, StrSpan> {
+/// A type name, the type of an array, a tuple of types, or the type of a function: \
+/// `medium` \
+/// `array[array[boolean]]` \
+/// `[medium, medium]` \
+/// `fn(medium, medium) -> boolean`
+pub(self) fn type_annotation(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("type_annotation" from i);
+    let (span, parsed) = alt((
+        map(
+            preceded(
+                verify(ident, |name| *name.fragment() == "array"),
+                delimited(
+                    punct(Punct::LBracket),
+                    context("a type", type_annotation),
+                    punct(Punct::RBracket),
+                ),
+            ),
+            |t| Token::ArrayType(box t),
+        ),
+        map(
+            delimited(
+                punct(Punct::LBracket),
+                separated_list1(punct(Punct::Comma), context("a type", type_annotation)),
+                punct(Punct::RBracket),
+            ),
+            Token::TupleType,
+        ),
+        map(
+            preceded(
+                keyword(Keyword::Fn),
+                tuple((
+                    delimited(
+                        punct(Punct::LParen),
+                        separated_list0(punct(Punct::Comma), context("a type", type_annotation)),
+                        punct(Punct::RParen),
+                    ),
+                    opt(preceded(
+                        punct(Punct::Arrow),
+                        context("a type", type_annotation),
+                    )),
+                )),
+            ),
+            |(params, returns)| Token::FunctionType {
+                params,
+                returns: returns.map(Box::new),
+            },
+        ),
+        map(ident, Token::Ident),
+    ))(i)?;
+
+    got!("type_annotation" from i);
+
+    return Ok((span, parsed));
+}
+
+/// A name with at least one index, as something to assign to: \
+/// `field[y][x]`
+pub(self) fn index(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    let (span, name) = ident(i)?;
+    match indexes(i, span, Token::Ident(name))? {
+        (span, parsed @ Token::Index { .. }) => Ok((span, parsed)),
+        _ => Err(nom::Err::Error(nom::error::ParseError::from_error_kind(
+            span,
+            ErrorKind::Verify,
+        ))),
+    }
+}
+
+/// Any number of `[index]`s right after the value that `start` begins with, which ends just before
+/// `i`. There can't be a space before the `[`, since `print [1]` passes an array instead.
+fn indexes(
+    start: Lexemes<'t, 'a>,
+    i: Lexemes<'t, 'a>,
+    mut value: Token<'a>,
+) -> ParseResult<'t, 'a, Token<'a>> {
+    let mut span = i;
+
+    loop {
+        let last = &start[start.len() - span.len() - 1].span;
+        let adjacent = span[0].span.location_offset() == last.location_offset() + last.len();
+        if span[0].kind != Kind::Punct(Punct::LBracket) || !adjacent {
+            return Ok((span, value));
+        }
+
+        let (rest, index) = delimited(
+            punct(Punct::LBracket),
+            context("an index", expression),
+            punct(Punct::RBracket),
+        )(span)?;
+
+        span = rest;
+        value = Token::Index {
+            value: box value,
+            index: box index,
+        };
+    }
+}
+
+fn ident(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, StrSpan<'a>> {
     attempt!("ident" from i);
-    let (span, parsed) = take_while1(|c: char| c.is_alphabetic())(i)?;
+    let (span, parsed) = match &i[0] {
+        Lexeme {
+            kind: Kind::Ident,
+            span,
+        } => (&i[1..], *span),
+        _ => return Err(nom::Err::Error(ParseError::new(i, "an identifier"))),
+    };
 
     got!("ident" from i);
 
     return Ok((span, parsed));
 }
 
-fn array(i: StrSpan) -> IResult<StrSpan, Token> {
+/// A string with values in it. Each value is lexed and parsed on its own, but keeps its place in
+/// the file, so anything wrong with it is pointed out inside the string.
+fn interpolation(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("interpolation" from i);
+    let pieces = match &i[0] {
+        Lexeme {
+            kind: Kind::Interpolation(pieces),
+            ..
+        } => pieces.clone(),
+        _ => return Err(nom::Err::Error(ParseError::new(i, "a string"))),
+    };
+
+    let mut segments = Vec::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text, span) => segments.push((span, Token::Literal(Literal::String(text)))),
+            Piece::Value(span) => {
+                let lexemes = lex_span(span);
+                let (rest, value) = context("a value", method)(&lexemes)?;
+                if rest[0].kind != Kind::Eof {
+                    return Err(nom::Err::Error(ParseError::new(rest, "\"}\"")));
+                }
+
+                segments.push((span, value));
+            }
+        }
+    }
+
+    got!("interpolation" from i);
+
+    return Ok((&i[1..], Token::Interpolation(segments)));
+}
+
+fn literal(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Literal<'a>> {
+    attempt!("literal" from i);
+    let (span, parsed) = match &i[0] {
+        Lexeme {
+            kind: Kind::Literal(literal),
+            ..
+        } => (&i[1..], literal.clone()),
+        _ => return Err(nom::Err::Error(ParseError::new(i, "a literal"))),
+    };
+
+    got!("literal" from i);
+
+    return Ok((span, parsed));
+}
+
+fn array(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("array" from i);
     let (span, parsed) = delimited(
-        pair(tag("["), space0),
-        separated_list1(tuple((space0, tag(","), space0)), token),
-        pair(space0, tag("]")),
+        punct(Punct::LBracket),
+        separated_list0(punct(Punct::Comma), token),
+        punct(Punct::RBracket),
     )(i)?;
 
     got!("array" from i);
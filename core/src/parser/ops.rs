@@ -1,240 +1,608 @@
 use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::{space0, space1};
-use nom::combinator::opt;
-use nom::error::ErrorKind;
-use nom::multi::separated_list0;
-use nom::sequence::{delimited, pair, tuple};
-use nom::IResult;
-use nom_locate::LocatedSpan;
+use nom::combinator::{map, opt};
+use nom::error::{context, ErrorKind};
+use nom::multi::{many0, separated_list0, separated_list1};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
 
-use crate::parser::util::{line_feed_whitespace, rest_of_file, StrSpan};
-use crate::parser::{param, singleton, TonsOfTokens};
+use crate::parser::error::ParseError;
+use crate::parser::lexer::{Keyword, Kind, Punct};
+use crate::parser::util::{keyword, punct, skip_comments, statements, Lexemes, ParseResult};
+use crate::parser::{index, param, singleton, stage, type_annotation, Pattern, TonsOfTokens};
 
-use super::{attempt, got, ident, token, Token};
+use super::{attempt, got, ident, literal, token, Token};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Op<'a> {
     Add(Box<Token<'a>>, Box<Token<'a>>),
     Subtract(Box<Token<'a>>, Box<Token<'a>>),
+    Multiply(Box<Token<'a>>, Box<Token<'a>>),
+
+    /// Integer division truncates toward zero, so `-7 / 2` is `-3`, and dividing an integer by
+    /// zero stops the program. Dividing by a literal `0` is an error when translating. Floats
+    /// divide as IEEE 754 does, and `1.0 / 0.0` is infinity rather than an error.
+    Divide(Box<Token<'a>>, Box<Token<'a>>),
+
+    /// The remainder of `Divide`, which takes the sign of the left operand, so `-7 % 2` is `-1`.
+    /// Only integers have a remainder: `%` on a float literal is an error when translating, and
+    /// V rejects it on any other float. By zero, it's the same as `Divide`.
+    Modulo(Box<Token<'a>>, Box<Token<'a>>),
+
     Eq(Box<Token<'a>>, Box<Token<'a>>),
     Neq(Box<Token<'a>>, Box<Token<'a>>),
     Gt(Box<Token<'a>>, Box<Token<'a>>),
     Lt(Box<Token<'a>>, Box<Token<'a>>),
     Gte(Box<Token<'a>>, Box<Token<'a>>),
     Lte(Box<Token<'a>>, Box<Token<'a>>),
+
+    /// Short-circuits: the right operand is only evaluated when the left one is true
+    And(Box<Token<'a>>, Box<Token<'a>>),
+
+    /// Short-circuits: the right operand is only evaluated when the left one is false
+    Or(Box<Token<'a>>, Box<Token<'a>>),
 }
 
-macro_rules! interop {
-    ($name: expr; $f: ident ($c: literal) -> $op: ident) => {
-        fn $f(i: StrSpan) -> IResult<StrSpan, Token> {
-            attempt!($name from i);
-            let (span, parsed) = nom::sequence::tuple((
-                crate::parser::singleton,
-                nom::character::complete::space0,
-                nom::bytes::complete::tag($c),
-                nom::character::complete::space0,
-                crate::parser::singleton,
-            ))(i)?;
-
-            got!($name from i);
-
-            return Ok((
-                span,
-                crate::parser::Token::Op(crate::parser::ops::Op::$op(box parsed.0, box parsed.4)),
-            ));
+/// An operation on a single operand, which binds tighter than any `Op`: \
+/// `!lit` \
+/// `-x`
+#[derive(Debug, PartialEq, Clone)]
+pub enum UnaryOp<'a> {
+    Not(Box<Token<'a>>),
+    Negate(Box<Token<'a>>),
+}
+
+/// Binary operators, from the tightest binding to the loosest. Ranges bind looser than all of
+/// them, so `a + 1..b` starts at `a + 1`: \
+/// `*` `/` `%` \
+/// `+` `-` \
+/// `==` `!=` `<` `>` `<=` `>=` \
+/// `&&` \
+/// `||`
+///
+/// Operators on the same row bind equally tightly, and all of them are left associative, so
+/// `a - b + c` is `(a - b) + c`.
+const PRECEDENCE: &[&[Punct]] = &[
+    &[Punct::Star, Punct::Slash, Punct::Percent],
+    &[Punct::Plus, Punct::Minus],
+    &[
+        Punct::EqEq,
+        Punct::NotEq,
+        Punct::Lt,
+        Punct::Gt,
+        Punct::LtEq,
+        Punct::GtEq,
+    ],
+    &[Punct::AndAnd],
+    &[Punct::OrOr],
+];
+
+impl Op<'a> {
+    pub fn operands(&self) -> (&Token<'a>, &Token<'a>) {
+        match self {
+            Op::Add(t1, t2)
+            | Op::Subtract(t1, t2)
+            | Op::Multiply(t1, t2)
+            | Op::Divide(t1, t2)
+            | Op::Modulo(t1, t2)
+            | Op::Eq(t1, t2)
+            | Op::Neq(t1, t2)
+            | Op::Gt(t1, t2)
+            | Op::Lt(t1, t2)
+            | Op::Gte(t1, t2)
+            | Op::Lte(t1, t2)
+            | Op::And(t1, t2)
+            | Op::Or(t1, t2) => (t1, t2),
         }
-    };
+    }
+
+    fn new(operator: Punct, t1: Token<'a>, t2: Token<'a>) -> Self {
+        match operator {
+            Punct::Plus => Op::Add(box t1, box t2),
+            Punct::Minus => Op::Subtract(box t1, box t2),
+            Punct::Star => Op::Multiply(box t1, box t2),
+            Punct::Slash => Op::Divide(box t1, box t2),
+            Punct::Percent => Op::Modulo(box t1, box t2),
+            Punct::EqEq => Op::Eq(box t1, box t2),
+            Punct::NotEq => Op::Neq(box t1, box t2),
+            Punct::Gt => Op::Gt(box t1, box t2),
+            Punct::Lt => Op::Lt(box t1, box t2),
+            Punct::GtEq => Op::Gte(box t1, box t2),
+            Punct::LtEq => Op::Lte(box t1, box t2),
+            Punct::AndAnd => Op::And(box t1, box t2),
+            Punct::OrOr => Op::Or(box t1, box t2),
+            _ => unreachable!("{operator} isn't a binary operator"),
+        }
+    }
+
+    pub fn operator(&self) -> Punct {
+        match self {
+            Op::Add(..) => Punct::Plus,
+            Op::Subtract(..) => Punct::Minus,
+            Op::Multiply(..) => Punct::Star,
+            Op::Divide(..) => Punct::Slash,
+            Op::Modulo(..) => Punct::Percent,
+            Op::Eq(..) => Punct::EqEq,
+            Op::Neq(..) => Punct::NotEq,
+            Op::Gt(..) => Punct::Gt,
+            Op::Lt(..) => Punct::Lt,
+            Op::Gte(..) => Punct::GtEq,
+            Op::Lte(..) => Punct::LtEq,
+            Op::And(..) => Punct::AndAnd,
+            Op::Or(..) => Punct::OrOr,
+        }
+    }
+
+    /// Higher binds tighter, see `PRECEDENCE`
+    pub fn precedence(&self) -> usize {
+        precedence(self.operator()).unwrap()
+    }
 }
 
-pub(super) fn method_def(i: StrSpan) -> IResult<StrSpan, Token> {
+impl UnaryOp<'a> {
+    pub fn operand(&self) -> &Token<'a> {
+        match self {
+            UnaryOp::Not(t) | UnaryOp::Negate(t) => t,
+        }
+    }
+
+    pub fn operator(&self) -> Punct {
+        match self {
+            UnaryOp::Not(_) => Punct::Bang,
+            UnaryOp::Negate(_) => Punct::Negate,
+        }
+    }
+}
+
+fn precedence(operator: Punct) -> Option<usize> {
+    PRECEDENCE
+        .iter()
+        .position(|row| row.contains(&operator))
+        .map(|row| PRECEDENCE.len() - row)
+}
+
+/// The binary operator at the start of `i`, if there is one, along with its precedence
+fn binary_operator(i: Lexemes) -> Option<(Punct, usize)> {
+    match i[0].kind {
+        Kind::Punct(p) => precedence(p).map(|prec| (p, prec)),
+        _ => None,
+    }
+}
+
+pub(super) fn method_def(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("method_def" from i);
     let (span, parsed) = tuple((
-        delimited(
-            pair(tag("|"), space0),
-            separated_list0(tuple((space0, tag(","), space0)), param),
-            pair(space0, tag("|")),
-        ),
-        space0,
-        bracket_group,
-        line_feed_whitespace,
-        rest_of_file,
+        alt((
+            // An empty parameter list is lexed as `||`
+            map(punct(Punct::OrOr), |_| Vec::new()),
+            delimited(
+                punct(Punct::Pipe),
+                separated_list0(punct(Punct::Comma), param),
+                punct(Punct::Pipe),
+            ),
+        )),
+        block,
     ))(i)?;
 
     got!("method_def" from i);
 
     return Ok((
         span,
-        Token::FnPair(TonsOfTokens(parsed.0), box parsed.2, box parsed.4),
+        Token::Function {
+            params: TonsOfTokens(parsed.0),
+            body: box parsed.1,
+        },
     ));
 }
 
-pub(super) fn method_unit(i: StrSpan) -> IResult<StrSpan, Token> {
-    attempt!("method_unit" from i);
-    let (span, parsed) = tuple((ident, space1, token, line_feed_whitespace, rest_of_file))(i)?;
+/// An unnamed function, which takes the types of its parameters from where it's used. Its body
+/// is one step of a pipeline, so a `;` after it hands on what the lambda is part of: \
+/// `|x| print x` \
+/// `eachwillbecome |row| length row; print`
+pub(super) fn lambda(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("lambda" from i);
+    let (span, parsed) = tuple((
+        delimited(
+            punct(Punct::Pipe),
+            separated_list1(punct(Punct::Comma), ident),
+            punct(Punct::Pipe),
+        ),
+        context("an expression", stage),
+    ))(i)?;
 
-    got!("method_unit" from i);
+    got!("lambda" from i);
 
     return Ok((
         span,
-        Token::Pair(
-            box Token::MethodUnit(box Token::Ident(parsed.0), box parsed.2),
-            box parsed.4,
-        ),
+        Token::Lambda {
+            params: parsed.0,
+            body: box parsed.1,
+        },
     ));
 }
 
-pub(super) fn method(i: StrSpan) -> IResult<StrSpan, Token> {
+/// A value passed through any number of steps, each taking the result of the one before it
+pub(super) fn method(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("method" from i);
-    let (span, parsed) = tuple((singleton, space0, tag(";"), space0, token))(i)?;
+    let (span, first) = stage(i)?;
+    let (span, steps) = many0(preceded(punct(Punct::Semicolon), context("a step", stage)))(span)?;
 
     got!("method" from i);
 
-    return Ok((span, Token::Method(box parsed.0, box parsed.4)));
+    return Ok((
+        span,
+        steps
+            .into_iter()
+            .fold(first, |object, step| Token::Method(box object, box step)),
+    ));
 }
 
-pub(super) fn ifstmt(i: StrSpan) -> IResult<StrSpan, Token> {
+pub(super) fn ifstmt(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("ifstmt" from i);
     let (span, parsed) = tuple((
-        tag("if"),
-        space0,
-        comparison,
-        space0,
-        bracket_group,
-        line_feed_whitespace,
-        rest_of_file,
+        keyword(Keyword::If),
+        expression,
+        block,
+        opt(preceded(keyword(Keyword::Else), alt((ifstmt, block)))),
     ))(i)?;
 
     got!("ifstmt" from i);
 
     return Ok((
         span,
-        Token::CondPair(
-            box Token::IfStmt { cond: box parsed.2 },
-            box parsed.4,
-            box parsed.6,
-        ),
+        Token::IfStmt {
+            cond: box parsed.1,
+            block: box parsed.2,
+            else_branch: parsed.3.map(|t| box t),
+        },
     ));
 }
 
-pub(super) fn assignment(i: StrSpan) -> IResult<StrSpan, Token> {
-    attempt!("assignment" from i);
+pub(super) fn loop_stmt(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("loop_stmt" from i);
     let (span, parsed) = tuple((
-        opt(pair(tag("mut"), space1)),
-        ident,
-        space0,
-        opt(pair(ident, space0)),
-        tag("="),
-        space0,
-        token,
-        line_feed_whitespace,
-        rest_of_file,
+        opt(terminated(ident, punct(Punct::Colon))),
+        alt((
+            map(keyword(Keyword::Loop), |_| None),
+            map(preceded(keyword(Keyword::While), expression), Some),
+        )),
+        block,
     ))(i)?;
 
-    got!("assignment" from i);
+    got!("loop_stmt" from i);
 
-    if let Some(t) = parsed.3 {
-        return Ok((
-            span,
-            Token::Pair(
-                box Token::Assignment {
-                    mutable: parsed.0.is_some(),
-                    type_annotation: Some(box Token::Ident(ident(parsed.1).unwrap().1)),
-                    ident: box Token::Ident(ident(t.0).unwrap().1),
-                    value: box parsed.6,
-                },
-                box parsed.8,
-            ),
-        ));
-    } else {
-        return Ok((
-            span,
-            Token::Pair(
-                box Token::Assignment {
-                    mutable: parsed.0.is_some(),
-                    type_annotation: None,
-                    ident: box Token::Ident(ident(parsed.1).unwrap().1),
-                    value: box parsed.6,
-                },
-                box parsed.8,
-            ),
-        ));
-    }
+    return Ok((
+        span,
+        Token::Loop {
+            label: parsed.0,
+            cond: parsed.1.map(|t| box t),
+            block: box parsed.2,
+        },
+    ));
 }
 
-//TODO: `LocatedSpan::from` using a slice is questionable... maybe??
-//      this function is also generally garbage
-fn bracket_group(i: StrSpan) -> IResult<StrSpan, Token> {
-    attempt!("bracket_group" from i);
-    let mut level = 1;
+/// `break` or `continue`, with an optional label
+pub(super) fn jump(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("jump" from i);
+    let (span, parsed) = alt((
+        map(
+            pair(keyword(Keyword::Break), opt(ident)),
+            |(keyword, label)| Token::Break { keyword, label },
+        ),
+        map(
+            pair(keyword(Keyword::Continue), opt(ident)),
+            |(keyword, label)| Token::Continue { keyword, label },
+        ),
+    ))(i)?;
 
-    if i.chars().nth(0) != Some('{') {
-        return Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Fail)));
-    }
-    for (u, c) in i.chars().enumerate() {
-        if u == 0 {
-            continue;
-        }
+    got!("jump" from i);
+
+    return Ok((span, parsed));
+}
+
+pub(super) fn return_stmt(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("return_stmt" from i);
+    let (span, parsed) = preceded(keyword(Keyword::Return), opt(token))(i)?;
+
+    got!("return_stmt" from i);
+
+    return Ok((span, Token::Return(parsed.map(|t| box t))));
+}
+
+pub(super) fn match_expr(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("match_expr" from i);
+    let (span, parsed) = tuple((
+        keyword(Keyword::Match),
+        expression,
+        delimited(punct(Punct::LBrace), arms, punct(Punct::RBrace)),
+    ))(i)?;
+
+    got!("match_expr" from i);
+
+    return Ok((
+        span,
+        Token::Match {
+            value: box parsed.1,
+            arms: parsed.2,
+        },
+    ));
+}
 
-        match c {
-            '{' => level += 1,
-            '}' => {
-                if level == 1 {
-                    match delimited(line_feed_whitespace, token, line_feed_whitespace)(
-                        LocatedSpan::from(&i[1..u]),
-                    ) {
-                        Ok((_, t)) => {
-                            got!("bracket_group" from i);
-                            return Ok((LocatedSpan::from(&i[(u + 1)..i.len()]), t));
-                        }
-
-                        Err(e) => return Err(e),
-                    }
-                } else {
-                    level -= 1;
-                }
-            }
-            _ => (),
+/// The `pattern: value` lines of a `match`, one per line
+fn arms(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Vec<(Pattern<'a>, Token<'a>)>> {
+    let mut arms = Vec::new();
+    let mut span = skip_comments(i);
+
+    loop {
+        if !arms.is_empty() && span[0].kind == Kind::Punct(Punct::RBrace) {
+            break;
         }
+
+        let (rest, parsed) = tuple((
+            context("a pattern", pattern),
+            punct(Punct::Colon),
+            context("a value", token),
+        ))(span)?;
+
+        // The last arm can sit right before the `}`
+        span = match rest[0].kind {
+            Kind::Newline => skip_comments(rest),
+            Kind::Punct(Punct::RBrace) => rest,
+            _ => return Err(nom::Err::Error(ParseError::new(rest, "a line break"))),
+        };
+
+        arms.push((parsed.0, parsed.2));
     }
 
-    return Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Fail)));
+    return Ok((span, arms));
+}
+
+/// A literal, a range of them, `_`, or a name to bind the value to
+fn pattern(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Pattern<'a>> {
+    attempt!("pattern" from i);
+    let (span, parsed) = alt((
+        map(
+            tuple((literal, range_operator, context("a literal", literal))),
+            |(start, inclusive, end)| Pattern::Range {
+                start,
+                end,
+                inclusive,
+            },
+        ),
+        map(literal, Pattern::Literal),
+        map(ident, |name| match *name.fragment() {
+            "_" => Pattern::Wildcard(name),
+            _ => Pattern::Binding(name),
+        }),
+    ))(i)?;
 
-    // Simple version that doesn't work :(
+    got!("pattern" from i);
 
-    /*
-    let (span, parsed) = delimited(
-        pair(tag("{"), line_feed_whitespace),
+    return Ok((span, parsed));
+}
+
+pub(super) fn group(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("group" from i);
+    let (span, parsed) = tuple((
+        keyword(Keyword::Group),
+        ident,
+        punct(Punct::Assign),
+        delimited(
+            punct(Punct::LBracket),
+            separated_list1(punct(Punct::Comma), context("a variant", ident)),
+            punct(Punct::RBracket),
+        ),
+    ))(i)?;
+
+    got!("group" from i);
+
+    return Ok((
+        span,
+        Token::Group {
+            name: parsed.1,
+            variants: parsed.3,
+        },
+    ));
+}
+
+/// `alias group/*` or `alias group/Variant`
+pub(super) fn alias(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("alias" from i);
+    let (span, parsed) = tuple((
+        keyword(Keyword::Alias),
+        ident,
+        punct(Punct::Slash),
+        alt((map(punct(Punct::Star), |_| None), map(ident, Some))),
+    ))(i)?;
+
+    got!("alias" from i);
+
+    return Ok((
+        span,
+        Token::Alias {
+            group: parsed.1,
+            variant: parsed.3,
+        },
+    ));
+}
+
+pub(super) fn assignment(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("assignment" from i);
+    let (span, parsed) = tuple((
+        alt((
+            map(
+                tuple((
+                    opt(keyword(Keyword::Mut)),
+                    type_annotation,
+                    ident,
+                    punct(Punct::Assign),
+                )),
+                |(mutable, t, name, _)| (mutable.is_some(), Some(box t), Token::Ident(name)),
+            ),
+            map(
+                tuple((opt(keyword(Keyword::Mut)), ident, punct(Punct::Assign))),
+                |(mutable, name, _)| (mutable.is_some(), None, Token::Ident(name)),
+            ),
+            // Takes apart a tuple: `[x, y] = [y, x]`
+            map(
+                tuple((
+                    opt(keyword(Keyword::Mut)),
+                    delimited(
+                        punct(Punct::LBracket),
+                        separated_list1(punct(Punct::Comma), map(ident, Token::Ident)),
+                        punct(Punct::RBracket),
+                    ),
+                    punct(Punct::Assign),
+                )),
+                |(mutable, names, _)| (mutable.is_some(), None, Token::Array(names)),
+            ),
+            // An element can only be changed, not declared
+            map(terminated(index, punct(Punct::Assign)), |t| {
+                (false, None, t)
+            }),
+        )),
         token,
-        pair(line_feed_whitespace, tag("}")),
-    )(i)?;
+    ))(i)?;
 
-    return Ok((span, parsed));*/
+    got!("assignment" from i);
+
+    let (mutable, type_annotation, target) = parsed.0;
+    return Ok((
+        span,
+        Token::Assignment {
+            doc: Vec::new(),
+            mutable,
+            type_annotation,
+            ident: box target,
+            value: box parsed.1,
+        },
+    ));
 }
 
-interop!("eq"; eq("==") -> Eq);
-interop!("neq"; neq("!=") -> Neq);
-interop!("gt"; gt(">") -> Gt);
-interop!("lt"; lt("<") -> Lt);
-interop!("gte"; gte(">=") -> Gte);
-interop!("lte"; lte("<=") -> Lte);
+/// A `{ }` delimited `Token::Block`
+fn block(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("block" from i);
+    let (span, parsed) = delimited(punct(Punct::LBrace), statements, punct(Punct::RBrace))(i)?;
+
+    got!("block" from i);
+
+    return Ok((span, Token::Block(parsed)));
+}
 
-pub fn comparison(i: StrSpan) -> IResult<StrSpan, Token> {
-    attempt!("comparison" from i);
-    let (span, parsed) = alt((eq, neq, gte, lte, gt, lt))(i)?;
+/// Any expression, with or without operators: \
+/// `x` \
+/// `(upper + lower) - 1`
+pub fn expression(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("expression" from i);
+    let (span, first) = unary(i)?;
+    let (span, parsed) = climb(span, first, 0)?;
 
-    got!("comparison" from i);
+    got!("expression" from i);
 
     return Ok((span, parsed));
 }
 
-interop!("addition"; addition("+") -> Add);
-interop!("subtraction"; subtraction("-") -> Subtract);
-
-pub fn ops(i: StrSpan) -> IResult<StrSpan, Token> {
+/// A value followed by operators, a name followed by the value it's called with, or just a
+/// value. The value at the start is only parsed once, however deep it goes, and what comes after
+/// it decides which one it is: \
+/// `x + 1` \
+/// `a + b == c` \
+/// `0..height` \
+/// `print x` \
+/// `!lit`
+pub fn ops(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("ops" from i);
-    let (span, parsed) = alt((subtraction, addition, comparison))(i)?;
+    let (span, first) = unary(i)?;
+
+    let (span, parsed) = if binary_operator(span).is_some() || range_operator(span).is_ok() {
+        let (span, parsed) = climb(span, first, 0)?;
+        match range_operator(span) {
+            Ok(_) => range_end(span, parsed)?,
+            Err(_) => (span, parsed),
+        }
+    } else {
+        match first {
+            Token::Ident(name) => match stage(span) {
+                Ok((span, arg)) => (span, Token::MethodUnit(box Token::Ident(name), box arg)),
+                Err(nom::Err::Error(_)) => (span, Token::Ident(name)),
+                Err(e) => return Err(e),
+            },
+            first => (span, first),
+        }
+    };
 
     got!("ops" from i);
     return Ok((span, parsed));
 }
+
+/// The rest of a range after its start
+fn range_end(i: Lexemes<'t, 'a>, start: Token<'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    let (span, (inclusive, end)) = pair(range_operator, context("a value", expression))(i)?;
+
+    return Ok((
+        span,
+        Token::Range {
+            start: box start,
+            end: box end,
+            inclusive,
+        },
+    ));
+}
+
+/// `..`, or `..=` when the range includes its end. Most values aren't followed by either, so
+/// not finding one isn't worth reporting.
+fn range_operator(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, bool> {
+    match i[0].kind {
+        Kind::Punct(Punct::DotDot) => Ok((&i[1..], false)),
+        Kind::Punct(Punct::DotDotEq) => Ok((&i[1..], true)),
+        _ => Err(nom::Err::Error(nom::error::ParseError::from_error_kind(
+            i,
+            ErrorKind::Tag,
+        ))),
+    }
+}
+
+/// Precedence climbing: folds every following operator that binds at least as tightly as
+/// `min_precedence` into `lhs`. When the operator after a right operand binds tighter than the
+/// one before it, that operand climbs first and takes the tighter operator with it.
+fn climb(
+    i: Lexemes<'t, 'a>,
+    mut lhs: Token<'a>,
+    min_precedence: usize,
+) -> ParseResult<'t, 'a, Token<'a>> {
+    let mut span = i;
+
+    while let Some((operator, prec)) = binary_operator(span).filter(|(_, p)| *p >= min_precedence) {
+        let (rest, mut rhs) = context("a value", unary)(&span[1..])?;
+        span = rest;
+
+        while let Some((_, next)) = binary_operator(span).filter(|(_, p)| *p > prec) {
+            let (rest, climbed) = climb(span, rhs, next)?;
+            span = rest;
+            rhs = climbed;
+        }
+
+        lhs = Token::Op(Op::new(operator, lhs, rhs));
+    }
+
+    return Ok((span, lhs));
+}
+
+/// A value with any number of prefix operators: \
+/// `x` \
+/// `!lit` \
+/// `-(a + b)`
+fn unary(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
+    attempt!("unary" from i);
+    let (span, parsed) = alt((
+        map(preceded(punct(Punct::Bang), context("a value", unary)), |t| {
+            Token::Unary(UnaryOp::Not(box t))
+        }),
+        map(preceded(punct(Punct::Negate), context("a value", unary)), |t| {
+            Token::Unary(UnaryOp::Negate(box t))
+        }),
+        singleton,
+    ))(i)?;
+
+    got!("unary" from i);
+
+    return Ok((span, parsed));
+}
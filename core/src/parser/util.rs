@@ -1,28 +1,184 @@
-use nom::branch::alt;
-use nom::bytes::complete::take_while;
-use nom::combinator::{map, rest};
+use nom::multi::many0;
 use nom::IResult;
 use nom_locate::LocatedSpan;
 
-use crate::parser::{token, Token};
+use crate::parser::error::{self, ParseError};
+use crate::parser::lexer::{Keyword, Kind, Lexeme, Punct};
+use crate::parser::{token, Stmt, Token};
 use crate::{attempt, got};
 
 pub(super) type StrSpan<'a> = LocatedSpan<&'a str, ()>;
 
-pub(super) fn line_feed_whitespace(i: StrSpan) -> IResult<StrSpan, StrSpan> {
-    attempt!("line_feed_whitespace" from i);
-    let (span, parsed) = take_while(|c| c == '\n' || c == ' ' || c == '\t')(i)?;
+/// The parser's input: what's left of the lexer's output. It always ends with `Kind::Eof`,
+/// which is never consumed, so there's always at least one lexeme to look at.
+pub(super) type Lexemes<'t, 'a> = &'t [Lexeme<'a>];
 
-    got!("line_feed_whitespace" from i);
+pub(super) type ParseResult<'t, 'a, O> = IResult<Lexemes<'t, 'a>, O, ParseError>;
 
-    return Ok((span, parsed));
+/// Matches a single piece of punctuation
+pub(super) fn punct(expected: Punct) -> impl Fn(Lexemes<'t, 'a>) -> ParseResult<'t, 'a, StrSpan<'a>>
+where
+    'a: 't,
+{
+    move |i| match &i[0] {
+        Lexeme {
+            kind: Kind::Punct(p),
+            span,
+        } if *p == expected => Ok((&i[1..], *span)),
+        _ => Err(nom::Err::Error(ParseError::new(
+            i,
+            &format!("\"{expected}\""),
+        ))),
+    }
+}
+
+/// Matches a single keyword
+pub(super) fn keyword(
+    expected: Keyword,
+) -> impl Fn(Lexemes<'t, 'a>) -> ParseResult<'t, 'a, StrSpan<'a>>
+where
+    'a: 't,
+{
+    move |i| match &i[0] {
+        Lexeme {
+            kind: Kind::Keyword(k),
+            span,
+        } if *k == expected => Ok((&i[1..], *span)),
+        _ => Err(nom::Err::Error(ParseError::new(
+            i,
+            &format!("\"{expected}\""),
+        ))),
+    }
 }
 
-pub(super) fn rest_of_file(i: StrSpan) -> IResult<StrSpan, Token> {
+/// Parses statements into a `Token::Block` until the file runs out. A statement that doesn't
+/// parse becomes a `Token::Error` and parsing picks up again on the next line, so every broken
+/// line gets reported.
+pub(super) fn rest_of_file(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Token<'a>> {
     attempt!("rest_of_file" from i);
-    let (span, parsed) = alt((token, map(rest, |_| Token::None)))(i)?;
+    let (span, statements) = statement_list(i, true)?;
 
     got!("rest_of_file" from i);
 
+    return Ok((span, Token::Block(statements)));
+}
+
+/// Parses statements up to the `}` that closes the current block, recovering like `rest_of_file`.
+pub(super) fn statements(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Vec<Stmt<'a>>> {
+    attempt!("statements" from i);
+    let (span, statements) = statement_list(i, false)?;
+
+    got!("statements" from i);
+
+    return Ok((span, statements));
+}
+
+fn statement_list(i: Lexemes<'t, 'a>, top_level: bool) -> ParseResult<'t, 'a, Vec<Stmt<'a>>> {
+    let mut statements = Vec::new();
+    let mut span = skip_newlines(i);
+
+    loop {
+        // Doc comments with nothing after them to document are plain comments
+        let next = skip_comments(span);
+        match next[0].kind {
+            Kind::Eof => {
+                span = next;
+                break;
+            }
+            Kind::Punct(Punct::RBrace) if !top_level => {
+                span = next;
+                break;
+            }
+            _ => (),
+        }
+
+        error::take_furthest();
+        match statement(span) {
+            Ok((rest, parsed)) => {
+                statements.push(parsed);
+                span = rest;
+            }
+
+            Err(nom::Err::Error(e)) => {
+                statements.push(Token::Error(
+                    error::take_furthest()
+                        .filter(|furthest| furthest.offset > e.offset)
+                        .unwrap_or(e),
+                ));
+                span = skip_statement(span);
+            }
+
+            Err(e) => return Err(e),
+        }
+
+        span = skip_newlines(span);
+    }
+
+    return Ok((span, statements));
+}
+
+fn statement(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, Stmt<'a>> {
+    attempt!("statement" from i);
+    let (span, lines) = many0(doc_comment)(i)?;
+    let (span, mut parsed) = token(span)?;
+
+    // Anything but an assignment has nowhere to keep its doc comment, so it's a plain comment
+    if let Token::Assignment { doc, .. } = &mut parsed {
+        *doc = lines;
+    }
+
+    // The last statement of a block can sit right before its `}`
+    let span = match span[0].kind {
+        Kind::Newline => &span[1..],
+        Kind::Eof | Kind::Punct(Punct::RBrace) => span,
+        _ => return Err(nom::Err::Error(ParseError::new(span, "a line break"))),
+    };
+
+    got!("statement" from i);
+
     return Ok((span, parsed));
 }
+
+/// One line of a doc comment, along with the line breaks after it
+fn doc_comment(i: Lexemes<'t, 'a>) -> ParseResult<'t, 'a, StrSpan<'a>> {
+    match &i[0] {
+        Lexeme {
+            kind: Kind::DocComment(text),
+            ..
+        } => Ok((skip_newlines(&i[1..]), *text)),
+        _ => Err(nom::Err::Error(ParseError::new(i, "a doc comment"))),
+    }
+}
+
+pub(super) fn skip_newlines(i: Lexemes<'t, 'a>) -> Lexemes<'t, 'a> {
+    let skipped = i.iter().take_while(|l| l.kind == Kind::Newline).count();
+    &i[skipped..]
+}
+
+/// Skips line breaks and doc comments, for where there's nothing a doc comment could document
+pub(super) fn skip_comments(i: Lexemes<'t, 'a>) -> Lexemes<'t, 'a> {
+    let skipped = i
+        .iter()
+        .take_while(|l| matches!(l.kind, Kind::Newline | Kind::DocComment(_)))
+        .count();
+    &i[skipped..]
+}
+
+/// Skips the rest of a broken statement, up to the next line break that isn't inside a block,
+/// or the `}` that closes the block it's in. At least one lexeme is always skipped, so a stray
+/// `}` at the top of the file can't stall the parser.
+fn skip_statement(i: Lexemes<'t, 'a>) -> Lexemes<'t, 'a> {
+    let mut level = 0;
+
+    for (u, lexeme) in i.iter().enumerate() {
+        match lexeme.kind {
+            Kind::Punct(Punct::LBrace) => level += 1,
+            Kind::Punct(Punct::RBrace) if level > 0 => level -= 1,
+            Kind::Punct(Punct::RBrace) | Kind::Newline if level == 0 && u > 0 => return &i[u..],
+            Kind::Eof => return &i[u..],
+            _ => (),
+        }
+    }
+
+    unreachable!("the lexer always ends with Kind::Eof")
+}